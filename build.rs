@@ -0,0 +1,25 @@
+//! Passes the versions of wgpu and winit that Cargo actually resolved on to the diagnostics, the
+//! crates themselves have no way to ask for them.
+
+use std::{env, fs, path::Path};
+
+fn main() {
+    let lockfile = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lockfile.display());
+    // Cargo writes it before running any build script, but be lenient in case it's somewhere else
+    let lockfile = fs::read_to_string(lockfile).unwrap_or_default();
+
+    for (krate, var) in [("wgpu", "WGPU_VERSION"), ("winit", "WINIT_VERSION")] {
+        let version = locked_version(&lockfile, krate).unwrap_or("unknown");
+        println!("cargo:rustc-env={var}={version}");
+    }
+}
+
+/// The version of the first package named `krate`, there's only one of each here.
+fn locked_version<'a>(lockfile: &'a str, krate: &str) -> Option<&'a str> {
+    let name = format!("name = \"{krate}\"");
+    let mut lines = lockfile.lines().skip_while(|line| *line != name);
+    lines.next()?;
+    let version = lines.next()?.strip_prefix("version = \"")?;
+    version.strip_suffix('"')
+}
@@ -0,0 +1,325 @@
+//! A separable Gaussian blur over everything rendered, in two passes through a pair of offscreen
+//! textures: the scene goes into the first, is blurred horizontally into the second, and from
+//! there vertically into the actual target.
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
+    BufferBindingType, BufferUsages, Color, ColorTargetState, ColorWrites, CommandEncoder, Device,
+    Extent3d, FilterMode, FragmentState, LoadOp, MultisampleState, Operations,
+    PipelineLayoutDescriptor, PrimitiveState, Queue, RenderPassColorAttachment,
+    RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerBindingType,
+    SamplerDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+    TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    memory::{self, TrackedTexture},
+    renderer::is_zero_sized,
+};
+
+/// Beyond that it's mostly slow, not any more blurry looking.
+pub const MAX_RADIUS: u32 = 32;
+
+/// Laid out the same as in the shader.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct Params {
+    /// One texel along the axis to blur along, in texture coordinates.
+    direction: [f32; 2],
+    /// In texels, 0 would be no blur at all.
+    radius: u32,
+    _padding: u32,
+}
+
+impl Params {
+    fn new(size: PhysicalSize<u32>, horizontal: bool, radius: u32) -> Self {
+        // might be zero-sized, in which case it's not used anyway
+        let size = [size.width.max(1) as f32, size.height.max(1) as f32];
+        let direction = if horizontal {
+            [1.0 / size[0], 0.0]
+        } else {
+            [0.0, 1.0 / size[1]]
+        };
+        Self {
+            direction,
+            radius,
+            _padding: 0,
+        }
+    }
+}
+
+pub struct Blur {
+    pipeline: RenderPipeline,
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    // one per pass
+    horizontal_params: Buffer,
+    vertical_params: Buffer,
+    format: TextureFormat,
+    /// Of the final target.
+    size: PhysicalSize<u32>,
+    radius: u32,
+    /// Missing while the target is zero-sized or the radius is 0, there's nothing to blur then.
+    targets: Option<Targets>,
+}
+
+/// The textures the passes ping-pong between, and how each pass samples them.
+struct Targets {
+    /// What the scene is rendered into.
+    input: TextureView,
+    /// What the horizontal pass renders into, and the vertical one reads from.
+    intermediate: TextureView,
+    horizontal: BindGroup,
+    vertical: BindGroup,
+    /// Behind the views, only kept to count them as allocated for as long as they're around.
+    _textures: [TrackedTexture; 2],
+}
+
+impl Blur {
+    /// `format` and `size` are the ones of the final target. Starts out disabled if `radius` is 0.
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        size: PhysicalSize<u32>,
+        radius: u32,
+    ) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("blur layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("blur shader"),
+            source: ShaderSource::Wgsl(include_str!("blur.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("blur pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("blur pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            // anything multisampled is resolved before it ends up here
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("blur sampler"),
+            // so the edges don't pick up the opposite side
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+        let radius = radius.min(MAX_RADIUS);
+        let horizontal_params = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("blur horizontal params"),
+            contents: bytemuck::bytes_of(&Params::new(size, true, radius)),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let vertical_params = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("blur vertical params"),
+            contents: bytemuck::bytes_of(&Params::new(size, false, radius)),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let mut blur = Self {
+            pipeline,
+            layout,
+            sampler,
+            horizontal_params,
+            vertical_params,
+            format,
+            size,
+            radius,
+            targets: None,
+        };
+        // most of the time there's no blur at all, no need to pay for the textures then
+        if radius > 0 {
+            blur.targets = blur.create_targets(device, size);
+        }
+        blur
+    }
+
+    /// Recreates the intermediate textures if there are any, they always have the size of the
+    /// final target.
+    pub fn resize(&mut self, device: &Device, queue: &Queue, size: PhysicalSize<u32>) {
+        self.size = size;
+        self.targets = None;
+        // one texel is a different distance now
+        self.set_radius(device, queue, self.radius);
+    }
+
+    /// Clamped to [`MAX_RADIUS`], 0 disables the blur entirely and frees the intermediate
+    /// textures.
+    pub fn set_radius(&mut self, device: &Device, queue: &Queue, radius: u32) {
+        self.radius = radius.min(MAX_RADIUS);
+        if self.radius == 0 {
+            self.targets = None;
+            return;
+        }
+        if self.targets.is_none() {
+            self.targets = self.create_targets(device, self.size);
+        }
+        let horizontal = Params::new(self.size, true, self.radius);
+        let vertical = Params::new(self.size, false, self.radius);
+        queue.write_buffer(&self.horizontal_params, 0, bytemuck::bytes_of(&horizontal));
+        queue.write_buffer(&self.vertical_params, 0, bytemuck::bytes_of(&vertical));
+    }
+
+    /// Where to render what should be blurred, in place of the final target. `None` if there's
+    /// no blur to apply.
+    pub fn input(&self) -> Option<&TextureView> {
+        Some(&self.targets.as_ref()?.input)
+    }
+
+    /// Blurs whatever was rendered into [`Self::input`] into `target`. Does nothing if there's no
+    /// input, in which case everything was rendered into `target` directly already.
+    pub fn apply(&self, encoder: &mut CommandEncoder, target: &TextureView) {
+        let Some(targets) = &self.targets else {
+            return;
+        };
+        self.pass(encoder, &targets.horizontal, &targets.intermediate);
+        self.pass(encoder, &targets.vertical, target);
+    }
+
+    fn pass(&self, encoder: &mut CommandEncoder, source: &BindGroup, target: &TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("blur pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: Operations {
+                    // every pixel is overwritten anyway
+                    load: LoadOp::Clear(Color::TRANSPARENT),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, source, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn create_targets(&self, device: &Device, size: PhysicalSize<u32>) -> Option<Targets> {
+        if is_zero_sized(size) {
+            return None;
+        }
+
+        let (input_texture, input) = create_target(device, self.format, size);
+        let (intermediate_texture, intermediate) = create_target(device, self.format, size);
+        let horizontal = self.create_bind_group(device, &input, &self.horizontal_params);
+        let vertical = self.create_bind_group(device, &intermediate, &self.vertical_params);
+        Some(Targets {
+            input,
+            intermediate,
+            horizontal,
+            vertical,
+            _textures: [input_texture, intermediate_texture],
+        })
+    }
+
+    fn create_bind_group(
+        &self,
+        device: &Device,
+        source: &TextureView,
+        params: &Buffer,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("blur bind group"),
+            layout: &self.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(source),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&self.sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+        })
+    }
+}
+
+fn create_target(
+    device: &Device,
+    format: TextureFormat,
+    size: PhysicalSize<u32>,
+) -> (TrackedTexture, TextureView) {
+    let texture = memory::create_texture(
+        device,
+        &TextureDescriptor {
+            label: Some("blur target"),
+            size: Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        },
+    );
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    (texture, view)
+}
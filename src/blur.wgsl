@@ -0,0 +1,45 @@
+struct Params {
+    // one texel along the axis to blur along, in texture coordinates
+    direction: vec2<f32>,
+    // in texels
+    radius: u32,
+}
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> params: Params;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // a single triangle covering the whole target
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(corner.x, 1.0 - corner.y);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // the kernel fades out to almost nothing at the radius
+    let radius = i32(params.radius);
+    let sigma = max(f32(radius) / 2.0, 0.5);
+    var sum = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var i = -radius; i <= radius; i++) {
+        let weight = exp(-f32(i * i) / (2.0 * sigma * sigma));
+        let uv = in.uv + params.direction * f32(i);
+        // no derivatives needed, so the loop can't make them undefined
+        sum += textureSampleLevel(source, source_sampler, uv, 0.0) * weight;
+        total += weight;
+    }
+    return sum / total;
+}
@@ -0,0 +1,70 @@
+//! A free-look camera for looking at the world-space geometry from anywhere.
+
+use std::f32::consts::FRAC_PI_2;
+
+use glam::{Mat4, Vec3};
+
+/// Vertical field of view, in radians.
+const FOV: f32 = 1.0;
+const NEAR: f32 = 0.1;
+const FAR: f32 = 100.0;
+/// In world units per second.
+const SPEED: f32 = 3.0;
+/// In radians per physical pixel the mouse moved.
+const SENSITIVITY: f32 = 0.002;
+/// Keeps the camera from flipping over when looking straight up or down.
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    position: Vec3,
+    /// Around the y axis, 0 looks along -z.
+    yaw: f32,
+    /// Positive looks up.
+    pitch: f32,
+    /// Width divided by height of the surface.
+    aspect: f32,
+}
+
+impl Camera {
+    pub fn new(aspect: f32) -> Self {
+        Self {
+            position: Vec3::ZERO,
+            yaw: 0.0,
+            pitch: 0.0,
+            aspect,
+        }
+    }
+
+    /// Has to be kept up to date with the surface size, or everything ends up stretched.
+    pub fn set_aspect(&mut self, aspect: f32) {
+        self.aspect = aspect;
+    }
+
+    /// Turns by how far the mouse moved, in physical pixels.
+    pub fn look(&mut self, dx: f64, dy: f64) {
+        self.yaw += dx as f32 * SENSITIVITY;
+        self.pitch = (self.pitch - dy as f32 * SENSITIVITY).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// `direction` is relative to where the camera is looking, x is to the right, y up and z
+    /// forward. Its length is ignored, so diagonal movement isn't any faster.
+    pub fn fly(&mut self, direction: Vec3, seconds: f32) {
+        // moving forward stays level, no matter how far up or down the camera looks
+        let forward = Vec3::new(self.yaw.sin(), 0.0, -self.yaw.cos());
+        let right = forward.cross(Vec3::Y);
+        let movement = right * direction.x + Vec3::Y * direction.y + forward * direction.z;
+        self.position += movement.normalize_or_zero() * SPEED * seconds;
+    }
+
+    pub fn view_projection(&self) -> Mat4 {
+        let forward = Vec3::new(
+            self.yaw.sin() * self.pitch.cos(),
+            self.pitch.sin(),
+            -self.yaw.cos() * self.pitch.cos(),
+        );
+        let view = Mat4::look_to_rh(self.position, forward, Vec3::Y);
+        let projection = Mat4::perspective_rh(FOV, self.aspect, NEAR, FAR);
+        projection * view
+    }
+}
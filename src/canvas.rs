@@ -0,0 +1,505 @@
+//! Lets fingers paint onto a texture that's kept between frames, so it accumulates everything
+//! drawn so far, and is composited over the scene. The strokes themselves are kept as well, so
+//! the last ones can be undone.
+
+use std::{collections::HashMap, mem};
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::{
+    vertex_attr_array, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendState,
+    Buffer, BufferAddress, BufferBindingType, BufferDescriptor, BufferUsages, Color,
+    ColorTargetState, ColorWrites, CommandEncoder, CommandEncoderDescriptor, Device, Extent3d,
+    FragmentState, ImageCopyTexture, LoadOp, MultisampleState, Operations, Origin3d,
+    PipelineLayoutDescriptor, PrimitiveState, Queue, RenderPass, RenderPassColorAttachment,
+    RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, ShaderModule,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, Texture, TextureAspect, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+    TextureViewDescriptor, TextureViewDimension, VertexBufferLayout, VertexState, VertexStepMode,
+};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::TouchPhase,
+};
+
+use crate::{
+    globals::Globals,
+    memory::{self, TrackedTexture},
+    touch,
+};
+
+/// sRGB, so it can be written to a PNG as is.
+const FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// After how many finished strokes the canvas is saved again, so undoing doesn't have to paint
+/// everything from the very first stroke on.
+const CHECKPOINT_INTERVAL: usize = 16;
+
+/// Everywhere a finger went from touching down to lifting off.
+struct Stroke {
+    /// Touch ID of the finger, which decides the color.
+    id: u64,
+    /// In physical pixels of the window.
+    points: Vec<[f32; 2]>,
+}
+
+impl Stroke {
+    /// A single dot for the first point, so even a tap leaves something behind, and a segment
+    /// to each following one.
+    fn segments(&self) -> impl Iterator<Item = Instance> + '_ {
+        let color = touch::color(self.id);
+        let starts = self.points.first().into_iter().chain(&self.points);
+        starts
+            .zip(&self.points)
+            .map(move |(&from, &to)| Instance { from, to, color })
+    }
+}
+
+/// How the canvas has to be prepared before the pending segments are painted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rebuild {
+    /// Everything on it stays.
+    No,
+    /// Back to what it was at the last checkpoint.
+    FromCheckpoint,
+    /// Cleared completely.
+    FromScratch,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Instance {
+    from: [f32; 2],
+    to: [f32; 2],
+    color: [f32; 4],
+}
+
+pub struct Canvas {
+    stroke_pipeline: RenderPipeline,
+    composite_pipeline: RenderPipeline,
+    /// Holds the canvas size for the stroke pipeline.
+    size_buffer: Buffer,
+    size_bind_group: BindGroup,
+    texture_layout: BindGroupLayout,
+    instances: Buffer,
+    capacity: usize,
+
+    strokes: Vec<Stroke>,
+    /// Indices into [`Self::strokes`] of the ones still going on, by touch ID.
+    active: HashMap<u64, usize>,
+    /// Still to be painted with the next [`Self::flush`].
+    pending: Vec<Instance>,
+    rebuild: Rebuild,
+
+    // only ever grows, so nothing painted is lost by making the window smaller for a moment
+    texture: TrackedTexture,
+    view: TextureView,
+    texture_bind_group: BindGroup,
+    /// The canvas with only the first [`Self::checkpointed`] strokes on it, all of them finished.
+    checkpoint: TrackedTexture,
+    checkpointed: usize,
+}
+
+impl Canvas {
+    /// `format` and `sample_count` are the ones of the target the canvas is composited onto.
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        size: PhysicalSize<u32>,
+        globals: &Globals,
+    ) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("canvas shader"),
+            source: ShaderSource::Wgsl(include_str!("canvas.wgsl").into()),
+        });
+
+        let size_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("canvas size layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let size_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("canvas size"),
+            size: mem::size_of::<[f32; 2]>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let size_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("canvas size bind group"),
+            layout: &size_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: size_buffer.as_entire_binding(),
+            }],
+        });
+        let stroke_pipeline = create_stroke_pipeline(device, &shader, &size_layout);
+
+        let texture_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("canvas texture layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+        let composite_pipeline = create_composite_pipeline(
+            device,
+            &shader,
+            format,
+            sample_count,
+            globals,
+            &texture_layout,
+        );
+
+        let capacity = 16;
+        let texture = create_texture(device, size);
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let texture_bind_group = create_texture_bind_group(device, &texture_layout, &view);
+        let checkpoint = create_texture(device, size);
+
+        Self {
+            stroke_pipeline,
+            composite_pipeline,
+            size_buffer,
+            size_bind_group,
+            texture_layout,
+            instances: create_instance_buffer(device, capacity),
+            capacity,
+            strokes: Vec::new(),
+            active: HashMap::new(),
+            pending: Vec::new(),
+            rebuild: Rebuild::No,
+            texture,
+            view,
+            texture_bind_group,
+            checkpoint,
+            checkpointed: 0,
+        }
+    }
+
+    /// Grows the canvas to at least `size`, keeping everything painted so far in the top left
+    /// corner.
+    pub fn resize(&mut self, device: &Device, queue: &Queue, size: PhysicalSize<u32>) {
+        let old_size = self.texture.size();
+        let size = PhysicalSize::new(
+            size.width.max(old_size.width),
+            size.height.max(old_size.height),
+        );
+        if size.width == old_size.width && size.height == old_size.height {
+            return;
+        }
+
+        // wgpu zeroes new textures, so the new area is transparent
+        let texture = create_texture(device, size);
+        let checkpoint = create_texture(device, size);
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("canvas resize"),
+        });
+        encoder.copy_texture_to_texture(image_copy(&self.texture), image_copy(&texture), old_size);
+        encoder.copy_texture_to_texture(
+            image_copy(&self.checkpoint),
+            image_copy(&checkpoint),
+            old_size,
+        );
+        queue.submit([encoder.finish()]);
+
+        self.view = texture.create_view(&TextureViewDescriptor::default());
+        self.texture_bind_group =
+            create_texture_bind_group(device, &self.texture_layout, &self.view);
+        self.texture = texture;
+        self.checkpoint = checkpoint;
+    }
+
+    /// Starts, continues or finishes the stroke of the finger with the given touch ID.
+    pub fn touch(&mut self, id: u64, phase: TouchPhase, location: PhysicalPosition<f64>) {
+        let point = [location.x as f32, location.y as f32];
+        match phase {
+            TouchPhase::Started => {
+                let stroke = Stroke {
+                    id,
+                    points: vec![point],
+                };
+                self.pending.extend(stroke.segments());
+                self.active.insert(id, self.strokes.len());
+                self.strokes.push(stroke);
+            }
+            TouchPhase::Moved => {
+                // might have been undone in the meantime
+                let Some(&index) = self.active.get(&id) else {
+                    return;
+                };
+                let stroke = &mut self.strokes[index];
+                let from = *stroke.points.last().expect("strokes start with a point");
+                stroke.points.push(point);
+                self.pending.push(Instance {
+                    from,
+                    to: point,
+                    color: touch::color(id),
+                });
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                self.active.remove(&id);
+            }
+        }
+    }
+
+    /// Removes the most recent stroke, even if it's still going on. Returns `false` if there was
+    /// nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        if self.strokes.pop().is_none() {
+            return false;
+        }
+        self.active
+            .retain(|_, &mut index| index < self.strokes.len());
+
+        // the checkpoint only helps if it doesn't contain the stroke that's gone now
+        let start = if self.strokes.len() >= self.checkpointed {
+            self.rebuild = Rebuild::FromCheckpoint;
+            self.checkpointed
+        } else {
+            self.rebuild = Rebuild::FromScratch;
+            self.checkpointed = 0;
+            0
+        };
+        self.pending = self.strokes[start..]
+            .iter()
+            .flat_map(Stroke::segments)
+            .collect();
+        true
+    }
+
+    /// Paints everything that happened since the last call into the canvas. Has to happen before
+    /// the frame [`Self::draw`] is called in is submitted.
+    pub fn flush(&mut self, device: &Device, queue: &Queue) {
+        if self.pending.is_empty() && self.rebuild == Rebuild::No {
+            return;
+        }
+
+        if self.pending.len() > self.capacity {
+            self.capacity = self.pending.len().next_power_of_two();
+            self.instances = create_instance_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.instances, 0, bytemuck::cast_slice(&self.pending));
+        let size = self.texture.size();
+        queue.write_buffer(
+            &self.size_buffer,
+            0,
+            bytemuck::cast_slice(&[size.width as f32, size.height as f32]),
+        );
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("canvas paint"),
+        });
+        if self.rebuild == Rebuild::FromCheckpoint {
+            encoder.copy_texture_to_texture(
+                image_copy(&self.checkpoint),
+                image_copy(&self.texture),
+                size,
+            );
+        }
+        let load = match self.rebuild {
+            Rebuild::FromScratch => LoadOp::Clear(Color::TRANSPARENT),
+            Rebuild::No | Rebuild::FromCheckpoint => LoadOp::Load,
+        };
+        self.paint(&mut encoder, load, self.pending.len() as u32);
+
+        // only finished strokes may end up in the checkpoint, otherwise an active one would be
+        // in there only halfway
+        if self.active.is_empty() && self.strokes.len() - self.checkpointed >= CHECKPOINT_INTERVAL {
+            encoder.copy_texture_to_texture(
+                image_copy(&self.texture),
+                image_copy(&self.checkpoint),
+                size,
+            );
+            self.checkpointed = self.strokes.len();
+        }
+        queue.submit([encoder.finish()]);
+
+        self.pending.clear();
+        self.rebuild = Rebuild::No;
+    }
+
+    /// Paints the first `count` uploaded segments.
+    fn paint(&self, encoder: &mut CommandEncoder, load: LoadOp<Color>, count: u32) {
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("canvas paint pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &self.view,
+                resolve_target: None,
+                ops: Operations { load, store: true },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.stroke_pipeline);
+        render_pass.set_bind_group(0, &self.size_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instances.slice(..));
+        render_pass.draw(0..6, 0..count);
+    }
+
+    /// Everything painted so far, at the canvas's own size, which is at least as large as the
+    /// window ever was.
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// Composites the canvas over what's been drawn so far, one texel per pixel from the top
+    /// left corner. Expects the globals to be bound at group 0 already.
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        render_pass.set_pipeline(&self.composite_pipeline);
+        render_pass.set_bind_group(1, &self.texture_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_stroke_pipeline(
+    device: &Device,
+    shader: &ShaderModule,
+    size_layout: &BindGroupLayout,
+) -> RenderPipeline {
+    let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("canvas stroke pipeline layout"),
+        bind_group_layouts: &[size_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("canvas stroke pipeline"),
+        layout: Some(&layout),
+        vertex: VertexState {
+            module: shader,
+            entry_point: "vs_stroke",
+            buffers: &[VertexBufferLayout {
+                array_stride: mem::size_of::<Instance>() as BufferAddress,
+                step_mode: VertexStepMode::Instance,
+                attributes: &vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4],
+            }],
+        },
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        // the canvas itself isn't multisampled, the edges are hard anyway
+        multisample: MultisampleState::default(),
+        fragment: Some(FragmentState {
+            module: shader,
+            entry_point: "fs_stroke",
+            targets: &[Some(ColorTargetState {
+                format: FORMAT,
+                // the strokes are opaque, so painting over the same spot twice changes nothing
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        multiview: None,
+    })
+}
+
+fn create_composite_pipeline(
+    device: &Device,
+    shader: &ShaderModule,
+    format: TextureFormat,
+    sample_count: u32,
+    globals: &Globals,
+    texture_layout: &BindGroupLayout,
+) -> RenderPipeline {
+    let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("canvas composite pipeline layout"),
+        bind_group_layouts: &[&globals.layout, texture_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("canvas composite pipeline"),
+        layout: Some(&layout),
+        vertex: VertexState {
+            module: shader,
+            entry_point: "vs_composite",
+            buffers: &[],
+        },
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState {
+            count: sample_count,
+            ..MultisampleState::default()
+        },
+        fragment: Some(FragmentState {
+            module: shader,
+            entry_point: "fs_composite",
+            targets: &[Some(ColorTargetState {
+                format,
+                blend: Some(BlendState::ALPHA_BLENDING),
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        multiview: None,
+    })
+}
+
+/// Even a minimized window gets a canvas, it just has to be grown later on.
+fn create_texture(device: &Device, size: PhysicalSize<u32>) -> TrackedTexture {
+    memory::create_texture(
+        device,
+        &TextureDescriptor {
+            label: Some("canvas"),
+            size: Extent3d {
+                width: size.width.max(1),
+                height: size.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC
+                | TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+    )
+}
+
+fn create_texture_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    view: &TextureView,
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("canvas texture bind group"),
+        layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: BindingResource::TextureView(view),
+        }],
+    })
+}
+
+fn create_instance_buffer(device: &Device, capacity: usize) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("canvas segments"),
+        size: (capacity * mem::size_of::<Instance>()) as BufferAddress,
+        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn image_copy(texture: &Texture) -> ImageCopyTexture<'_> {
+    ImageCopyTexture {
+        texture,
+        mip_level: 0,
+        origin: Origin3d::ZERO,
+        aspect: TextureAspect::All,
+    }
+}
@@ -0,0 +1,86 @@
+// strokes are rendered into the canvas texture, which is then composited over each frame
+
+// radius of the strokes, in physical pixels
+const RADIUS: f32 = 6.0;
+
+@group(0) @binding(0)
+var<uniform> canvas_size: vec2<f32>;
+
+struct Segment {
+    // both in physical pixels from the top left corner of the canvas
+    @location(0) start: vec2<f32>,
+    @location(1) end: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct StrokeOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) start: vec2<f32>,
+    @location(1) end: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+@vertex
+fn vs_stroke(@builtin(vertex_index) index: u32, segment: Segment) -> StrokeOutput {
+    // a quad around the segment, large enough for the round caps on both ends
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[index];
+
+    let along = segment.end - segment.start;
+    let len = length(along);
+    // a single touch without movement is still a dot
+    let direction = select(vec2<f32>(1.0, 0.0), along / len, len > 0.0);
+    let normal = vec2<f32>(-direction.y, direction.x);
+    let tip = select(segment.start, segment.end, corner.x > 0.0);
+    let pixel = tip + (direction * corner.x + normal * corner.y) * RADIUS;
+
+    var out: StrokeOutput;
+    out.position = vec4<f32>(
+        pixel.x / canvas_size.x * 2.0 - 1.0,
+        1.0 - pixel.y / canvas_size.y * 2.0,
+        0.0,
+        1.0,
+    );
+    out.start = segment.start;
+    out.end = segment.end;
+    out.color = segment.color;
+    return out;
+}
+
+@fragment
+fn fs_stroke(in: StrokeOutput) -> @location(0) vec4<f32> {
+    // distance to the closest point on the segment, which rounds off the corners of the quad
+    let along = in.end - in.start;
+    let t = clamp(dot(in.position.xy - in.start, along) / max(dot(along, along), 1e-6), 0.0, 1.0);
+    if distance(in.position.xy, in.start + along * t) > RADIUS {
+        discard;
+    }
+    return in.color;
+}
+
+@group(1) @binding(0)
+var canvas: texture_2d<f32>;
+
+@vertex
+fn vs_composite(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // one triangle large enough to cover the whole screen, the parts outside are clipped
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_composite(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    // one canvas texel per pixel, the canvas might be larger than the target but never smaller
+    let texel = vec2<i32>(position.xy);
+    if any(texel >= vec2<i32>(textureDimensions(canvas))) {
+        discard;
+    }
+    return textureLoad(canvas, texel, 0);
+}
@@ -0,0 +1,66 @@
+//! Optional settings file, so one doesn't have to pass the same flags on every launch.
+
+use std::{fs, io, path::Path};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use winit::dpi::PhysicalSize;
+
+use crate::{parse_clear_color, parse_power_preference, parse_present_mode, Args};
+
+/// Everything is optional, and anything passed on the command line wins over what's in here.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Same format as `--clear-color`.
+    clear_color: Option<String>,
+    /// Same format as `--present-mode`.
+    present_mode: Option<String>,
+    /// Same format as `--power`.
+    power_preference: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    title: Option<String>,
+}
+
+impl Config {
+    /// A missing file is the same as an empty one.
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Could not read {}", path.display()))
+            }
+        };
+
+        toml::from_str(&source).with_context(|| format!("Could not parse {}", path.display()))
+    }
+
+    /// Fills in everything in `args` that wasn't given explicitly.
+    pub fn apply_to(self, args: &mut Args) -> Result<()> {
+        if let (None, Some(color)) = (args.clear_color, self.clear_color) {
+            args.clear_color = Some(parse_clear_color(&color).context("Invalid `clear_color`")?);
+        }
+        if let (None, Some(mode)) = (args.present_mode, self.present_mode) {
+            args.present_mode = Some(parse_present_mode(&mode).context("Invalid `present_mode`")?);
+        }
+        if let (None, Some(power)) = (args.power, self.power_preference) {
+            args.power =
+                Some(parse_power_preference(&power).context("Invalid `power_preference`")?);
+        }
+        match (self.width, self.height) {
+            (Some(0), _) | (_, Some(0)) => bail!("`width` and `height` must be positive"),
+            (Some(width), Some(height)) => {
+                args.size = args.size.or(Some(PhysicalSize::new(width, height)));
+            }
+            (None, None) => (),
+            _ => bail!("`width` and `height` have to be set together"),
+        }
+        if args.title.is_none() {
+            args.title = self.title;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,213 @@
+//! Geometry that needs depth testing to look right, like two overlapping triangles at different
+//! depths, so it's visible right away whether occlusion works, or the world around the camera.
+
+use pollster::FutureExt;
+use tracing::warn;
+use wgpu::{
+    BindGroup, ColorTargetState, ColorWrites, CommandEncoder, CompareFunction, DepthBiasState,
+    DepthStencilState, Device, ErrorFilter, Extent3d, FragmentState, LoadOp, MultisampleState,
+    Operations, PipelineLayoutDescriptor, PrimitiveState, RenderPassColorAttachment,
+    RenderPassDepthStencilAttachment, RenderPassDescriptor, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, StencilState,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView,
+    TextureViewDescriptor, VertexState,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    globals::Globals,
+    memory::{self, TrackedTexture},
+    renderer::is_zero_sized,
+};
+
+const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+
+/// What to draw, each comes with its own builtin shader.
+#[derive(Debug, Clone, Copy)]
+pub enum Shader {
+    /// Two overlapping triangles, the nearer one drawn first.
+    Overlap,
+    /// A ring of triangles around the origin and a floor, positioned by the camera in the
+    /// globals.
+    World,
+}
+
+impl Shader {
+    fn source(self) -> &'static str {
+        match self {
+            Self::Overlap => include_str!("depth.wgsl"),
+            Self::World => include_str!("world.wgsl"),
+        }
+    }
+
+    fn vertex_count(self) -> u32 {
+        match self {
+            Self::Overlap => 6,
+            // 8 triangles in the ring and 2 for the floor
+            Self::World => 30,
+        }
+    }
+}
+
+pub struct DepthGeometry {
+    pipeline: RenderPipeline,
+    vertex_count: u32,
+    sample_count: u32,
+    // both missing while the surface is zero-sized
+    texture: Option<TrackedTexture>,
+    view: Option<TextureView>,
+}
+
+impl DepthGeometry {
+    /// Returns `None` and logs why if the device can't do depth testing like this, since
+    /// everything else works just fine without it.
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        size: PhysicalSize<u32>,
+        sample_count: u32,
+        globals: &Globals,
+        shader: Shader,
+    ) -> Option<Self> {
+        // not every format and sample count combination is guaranteed to be usable
+        device.push_error_scope(ErrorFilter::Validation);
+        let pipeline = create_pipeline(device, format, sample_count, globals, shader.source());
+        let (texture, view) = create_depth_target(device, size, sample_count).unzip();
+        if let Some(err) = device.pop_error_scope().block_on() {
+            warn!("Could not set up depth testing, leaving it out: {err}");
+            return None;
+        }
+
+        Some(Self {
+            pipeline,
+            vertex_count: shader.vertex_count(),
+            sample_count,
+            texture,
+            view,
+        })
+    }
+
+    pub fn resize(&mut self, device: &Device, size: PhysicalSize<u32>) {
+        (self.texture, self.view) = create_depth_target(device, size, self.sample_count).unzip();
+    }
+
+    /// Draws in a pass of its own over whatever is in `view` already. `view` and `resolve_target`
+    /// are the same as for the main pass.
+    pub fn draw(
+        &self,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        resolve_target: Option<&TextureView>,
+        globals: &BindGroup,
+    ) {
+        let Some(depth_view) = &self.view else {
+            return;
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("depth pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    // only needed for this very pass
+                    store: false,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        render_pass.set_bind_group(0, globals, &[]);
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+fn create_pipeline(
+    device: &Device,
+    format: TextureFormat,
+    sample_count: u32,
+    globals: &Globals,
+    source: &'static str,
+) -> RenderPipeline {
+    let shader = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("depth shader"),
+        source: ShaderSource::Wgsl(source.into()),
+    });
+    let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("depth pipeline layout"),
+        bind_group_layouts: &[&globals.layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("depth pipeline"),
+        layout: Some(&layout),
+        vertex: VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        primitive: PrimitiveState::default(),
+        depth_stencil: Some(DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: CompareFunction::Less,
+            stencil: StencilState::default(),
+            bias: DepthBiasState::default(),
+        }),
+        multisample: MultisampleState {
+            count: sample_count,
+            ..MultisampleState::default()
+        },
+        fragment: Some(FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(ColorTargetState {
+                format,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        multiview: None,
+    })
+}
+
+fn create_depth_target(
+    device: &Device,
+    size: PhysicalSize<u32>,
+    sample_count: u32,
+) -> Option<(TrackedTexture, TextureView)> {
+    if is_zero_sized(size) {
+        return None;
+    }
+
+    let texture = memory::create_texture(
+        device,
+        &TextureDescriptor {
+            label: Some("depth target"),
+            size: Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            // has to match the color target
+            sample_count,
+            dimension: TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+    );
+    let view = texture.create_view(&TextureViewDescriptor::default());
+
+    Some((texture, view))
+}
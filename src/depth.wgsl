@@ -0,0 +1,52 @@
+struct Globals {
+    time: f32,
+    grid_size: f32,
+    resolution: vec2<f32>,
+    gradient_start: vec4<f32>,
+    gradient_end: vec4<f32>,
+    gradient_horizontal: u32,
+    zoom: f32,
+    pan: vec2<f32>,
+    view_projection: mat4x4<f32>,
+    pixel_projection: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // the near triangle comes first, so without depth testing the far one would be drawn over it
+    var positions = array<vec3<f32>, 6>(
+        vec3<f32>(-0.2, 0.5, 0.25),
+        vec3<f32>(-0.7, -0.4, 0.25),
+        vec3<f32>(0.3, -0.4, 0.25),
+        vec3<f32>(0.2, 0.4, 0.75),
+        vec3<f32>(-0.3, -0.5, 0.75),
+        vec3<f32>(0.7, -0.5, 0.75),
+    );
+    var colors = array<vec3<f32>, 2>(
+        vec3<f32>(1.0, 0.5, 0.1),
+        vec3<f32>(0.2, 0.4, 1.0),
+    );
+    let position = positions[index];
+
+    // same as for the triangle
+    let aspect = globals.resolution.x / max(globals.resolution.y, 1.0);
+    let scale = select(vec2<f32>(1.0, aspect), vec2<f32>(1.0 / aspect, 1.0), aspect > 1.0);
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(position.xy * scale * globals.zoom + globals.pan, position.z, 1.0);
+    out.color = colors[index / 3u];
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
@@ -0,0 +1,201 @@
+//! Everything interesting about the setup in one JSON object, so it can be collected and compared
+//! across machines.
+
+use std::fs;
+
+use serde::Serialize;
+use wgpu::{Adapter, Device, Limits, PresentMode, TextureFormat};
+
+// there's no way to ask the crates themselves, so build.rs takes them from Cargo.lock
+const WGPU_VERSION: &str = env!("WGPU_VERSION");
+const WINIT_VERSION: &str = env!("WINIT_VERSION");
+
+#[derive(Serialize)]
+pub struct Diagnostics {
+    adapter: AdapterDiagnostics,
+    surface_format: String,
+    present_mode: String,
+    /// Only the ones actually enabled on the device, not everything the adapter supports.
+    features: Vec<String>,
+    limits: LimitDiagnostics,
+    os: &'static str,
+    /// `None` where it's not known how to find out.
+    os_version: Option<String>,
+    arch: &'static str,
+    app_version: &'static str,
+    wgpu_version: &'static str,
+    winit_version: &'static str,
+}
+
+#[derive(Serialize)]
+struct AdapterDiagnostics {
+    name: String,
+    vendor: usize,
+    device: usize,
+    device_type: String,
+    driver: String,
+    driver_info: String,
+    backend: String,
+}
+
+/// All of them, mirrored since [`Limits`] is only serializable with wgpu's tracing enabled.
+#[derive(Serialize)]
+struct LimitDiagnostics {
+    max_texture_dimension_1d: u32,
+    max_texture_dimension_2d: u32,
+    max_texture_dimension_3d: u32,
+    max_texture_array_layers: u32,
+    max_bind_groups: u32,
+    max_bindings_per_bind_group: u32,
+    max_dynamic_uniform_buffers_per_pipeline_layout: u32,
+    max_dynamic_storage_buffers_per_pipeline_layout: u32,
+    max_sampled_textures_per_shader_stage: u32,
+    max_samplers_per_shader_stage: u32,
+    max_storage_buffers_per_shader_stage: u32,
+    max_storage_textures_per_shader_stage: u32,
+    max_uniform_buffers_per_shader_stage: u32,
+    max_uniform_buffer_binding_size: u32,
+    max_storage_buffer_binding_size: u32,
+    max_vertex_buffers: u32,
+    max_buffer_size: u64,
+    max_vertex_attributes: u32,
+    max_vertex_buffer_array_stride: u32,
+    min_uniform_buffer_offset_alignment: u32,
+    min_storage_buffer_offset_alignment: u32,
+    max_inter_stage_shader_components: u32,
+    max_compute_workgroup_storage_size: u32,
+    max_compute_invocations_per_workgroup: u32,
+    max_compute_workgroup_size_x: u32,
+    max_compute_workgroup_size_y: u32,
+    max_compute_workgroup_size_z: u32,
+    max_compute_workgroups_per_dimension: u32,
+    max_push_constant_size: u32,
+}
+
+impl Diagnostics {
+    pub fn collect(
+        adapter: &Adapter,
+        device: &Device,
+        surface_format: TextureFormat,
+        present_mode: PresentMode,
+    ) -> Self {
+        let info = adapter.get_info();
+
+        Self {
+            adapter: AdapterDiagnostics {
+                name: info.name,
+                vendor: info.vendor,
+                device: info.device,
+                device_type: format!("{:?}", info.device_type),
+                driver: info.driver,
+                driver_info: info.driver_info,
+                backend: format!("{:?}", info.backend),
+            },
+            surface_format: format!("{surface_format:?}"),
+            present_mode: format!("{present_mode:?}"),
+            features: device
+                .features()
+                .iter_names()
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            limits: device.limits().into(),
+            os: std::env::consts::OS,
+            os_version: os_version(),
+            arch: std::env::consts::ARCH,
+            app_version: env!("CARGO_PKG_VERSION"),
+            wgpu_version: WGPU_VERSION,
+            winit_version: WINIT_VERSION,
+        }
+    }
+}
+
+impl From<Limits> for LimitDiagnostics {
+    fn from(limits: Limits) -> Self {
+        // destructured, so a limit added in a new wgpu version can't be forgotten here
+        let Limits {
+            max_texture_dimension_1d,
+            max_texture_dimension_2d,
+            max_texture_dimension_3d,
+            max_texture_array_layers,
+            max_bind_groups,
+            max_bindings_per_bind_group,
+            max_dynamic_uniform_buffers_per_pipeline_layout,
+            max_dynamic_storage_buffers_per_pipeline_layout,
+            max_sampled_textures_per_shader_stage,
+            max_samplers_per_shader_stage,
+            max_storage_buffers_per_shader_stage,
+            max_storage_textures_per_shader_stage,
+            max_uniform_buffers_per_shader_stage,
+            max_uniform_buffer_binding_size,
+            max_storage_buffer_binding_size,
+            max_vertex_buffers,
+            max_buffer_size,
+            max_vertex_attributes,
+            max_vertex_buffer_array_stride,
+            min_uniform_buffer_offset_alignment,
+            min_storage_buffer_offset_alignment,
+            max_inter_stage_shader_components,
+            max_compute_workgroup_storage_size,
+            max_compute_invocations_per_workgroup,
+            max_compute_workgroup_size_x,
+            max_compute_workgroup_size_y,
+            max_compute_workgroup_size_z,
+            max_compute_workgroups_per_dimension,
+            max_push_constant_size,
+        } = limits;
+        Self {
+            max_texture_dimension_1d,
+            max_texture_dimension_2d,
+            max_texture_dimension_3d,
+            max_texture_array_layers,
+            max_bind_groups,
+            max_bindings_per_bind_group,
+            max_dynamic_uniform_buffers_per_pipeline_layout,
+            max_dynamic_storage_buffers_per_pipeline_layout,
+            max_sampled_textures_per_shader_stage,
+            max_samplers_per_shader_stage,
+            max_storage_buffers_per_shader_stage,
+            max_storage_textures_per_shader_stage,
+            max_uniform_buffers_per_shader_stage,
+            max_uniform_buffer_binding_size,
+            max_storage_buffer_binding_size,
+            max_vertex_buffers,
+            max_buffer_size,
+            max_vertex_attributes,
+            max_vertex_buffer_array_stride,
+            min_uniform_buffer_offset_alignment,
+            min_storage_buffer_offset_alignment,
+            max_inter_stage_shader_components,
+            max_compute_workgroup_storage_size,
+            max_compute_invocations_per_workgroup,
+            max_compute_workgroup_size_x,
+            max_compute_workgroup_size_y,
+            max_compute_workgroup_size_z,
+            max_compute_workgroups_per_dimension,
+            max_push_constant_size,
+        }
+    }
+}
+
+/// Like `Arch Linux (6.5.9-arch2-1)`, the distribution and the kernel. Only known on Linux for now.
+fn os_version() -> Option<String> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+
+    let kernel = fs::read_to_string("/proc/sys/kernel/osrelease").ok();
+    let kernel = kernel.as_deref().map(str::trim);
+    let os_release = fs::read_to_string("/etc/os-release").ok();
+    let distribution = os_release.as_deref().and_then(|os_release| {
+        let name = os_release
+            .lines()
+            .find_map(|line| line.strip_prefix("PRETTY_NAME="))?;
+        Some(name.trim_matches('"'))
+    });
+
+    match (distribution, kernel) {
+        (Some(distribution), Some(kernel)) => Some(format!("{distribution} ({kernel})")),
+        (Some(version), None) | (None, Some(version)) => Some(version.to_string()),
+        (None, None) => None,
+    }
+}
@@ -0,0 +1,164 @@
+//! Per-frame values every shader can read from bind group 0.
+
+use std::{mem, time::Duration};
+
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, Buffer, BufferAddress, BufferBindingType, BufferDescriptor,
+    BufferUsages, Color, Device, Queue, ShaderStages,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::gradient::Gradient;
+
+/// Mirrors `Globals` in the shaders, so watch the WGSL alignment rules when changing this.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Uniforms {
+    /// Seconds since startup.
+    time: f32,
+    /// Edge length of a background grid cell in physical pixels, 0 if there's no grid.
+    grid_size: f32,
+    /// Surface size in physical pixels.
+    resolution: [f32; 2],
+    gradient_start: [f32; 4],
+    gradient_end: [f32; 4],
+    /// Actually a bool, 0 is vertical.
+    gradient_horizontal: u32,
+    zoom: f32,
+    /// In normalized device coordinates.
+    pan: [f32; 2],
+    /// Of the free-look camera, only used by the world-space geometry.
+    view_projection: [[f32; 4]; 4],
+    /// From physical pixels with the origin in the top left corner to NDC.
+    pixel_projection: [[f32; 4]; 4],
+}
+
+/// How far the scene is zoomed in and moved, the backgrounds and touches stay where they are.
+#[derive(Debug, Clone, Copy)]
+pub struct View {
+    pub zoom: f32,
+    /// In normalized device coordinates, applied after zooming.
+    pub pan: [f32; 2],
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self {
+            zoom: 1.0,
+            pan: [0.0, 0.0],
+        }
+    }
+}
+
+impl View {
+    /// Scales by `factor` while keeping whatever is at `anchor` (in normalized device
+    /// coordinates) in place.
+    pub fn zoom_around(&mut self, anchor: [f32; 2], factor: f32) {
+        let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        // might be less than asked for due to the clamping
+        let applied = zoom / self.zoom;
+        for (pan, anchor) in self.pan.iter_mut().zip(anchor) {
+            *pan = anchor - (anchor - *pan) * applied;
+        }
+        self.zoom = zoom;
+    }
+}
+
+const MIN_ZOOM: f32 = 0.1;
+const MAX_ZOOM: f32 = 50.0;
+
+pub struct Globals {
+    buffer: Buffer,
+    grid_size: f32,
+    gradient: Option<Gradient>,
+    view: View,
+    view_projection: Mat4,
+    pub layout: BindGroupLayout,
+    pub bind_group: BindGroup,
+}
+
+impl Globals {
+    pub fn new(device: &Device, grid_size: Option<u32>, gradient: Option<Gradient>) -> Self {
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("globals"),
+            size: mem::size_of::<Uniforms>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("globals layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX_FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("globals bind group"),
+            layout: &layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            buffer,
+            grid_size: grid_size.map_or(0.0, |size| size as f32),
+            gradient,
+            view: View::default(),
+            view_projection: Mat4::IDENTITY,
+            layout,
+            bind_group,
+        }
+    }
+
+    /// Takes effect with the next [`Self::update`].
+    pub fn set_view(&mut self, view: View) {
+        self.view = view;
+    }
+
+    /// Takes effect with the next [`Self::update`].
+    pub fn set_view_projection(&mut self, view_projection: Mat4) {
+        self.view_projection = view_projection;
+    }
+
+    pub fn update(&self, queue: &Queue, elapsed: Duration, size: PhysicalSize<u32>) {
+        let mut uniforms = Uniforms {
+            time: elapsed.as_secs_f32(),
+            grid_size: self.grid_size,
+            resolution: [size.width as f32, size.height as f32],
+            zoom: self.view.zoom,
+            pan: self.view.pan,
+            view_projection: self.view_projection.to_cols_array_2d(),
+            pixel_projection: pixel_projection(size).to_cols_array_2d(),
+            // unused without a gradient
+            ..Uniforms::zeroed()
+        };
+        if let Some(gradient) = self.gradient {
+            uniforms.gradient_start = color_to_array(gradient.start);
+            uniforms.gradient_end = color_to_array(gradient.end);
+            uniforms.gradient_horizontal = gradient.horizontal.into();
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+}
+
+fn pixel_projection(size: PhysicalSize<u32>) -> Mat4 {
+    // y points down in pixels, but up in NDC
+    Mat4::orthographic_rh(0.0, size.width as f32, size.height as f32, 0.0, -1.0, 1.0)
+}
+
+fn color_to_array(color: Color) -> [f32; 4] {
+    [color.r, color.g, color.b, color.a].map(|channel| channel as f32)
+}
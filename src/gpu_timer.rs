@@ -0,0 +1,134 @@
+//! Measures how long the GPU spends on a frame using timestamp queries.
+
+use std::{
+    mem,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tracing::warn;
+use wgpu::{
+    Buffer, BufferAddress, BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoder,
+    Device, Features, Maintain, MapMode, QuerySet, QuerySetDescriptor, QueryType, Queue,
+};
+
+const QUERY_COUNT: u32 = 2;
+const QUERY_BYTES: BufferAddress = QUERY_COUNT as BufferAddress * mem::size_of::<u64>() as u64;
+
+/// Readback happens asynchronously, so the reported time is usually from a frame or two ago.
+pub struct GpuTimer {
+    queries: QuerySet,
+    resolve_buffer: Buffer,
+    readback_buffer: Buffer,
+    /// Nanoseconds per timestamp tick.
+    period: f32,
+
+    /// Whether the readback buffer was written to by the frame being recorded right now.
+    copy_pending: bool,
+    /// Whether the readback buffer is currently mapped or waiting to be so.
+    in_flight: bool,
+    /// Set by the callback of the mapping once it's done, successfully or not.
+    mapped: Arc<Mutex<Option<Result<(), BufferAsyncError>>>>,
+}
+
+impl GpuTimer {
+    /// Returns `None` if the device doesn't support timestamp queries.
+    pub fn new(device: &Device, queue: &Queue) -> Option<Self> {
+        if !device.features().contains(Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let queries = device.create_query_set(&QuerySetDescriptor {
+            label: Some("frame timestamps"),
+            ty: QueryType::Timestamp,
+            count: QUERY_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("timestamp resolve buffer"),
+            size: QUERY_BYTES,
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("timestamp readback buffer"),
+            size: QUERY_BYTES,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            queries,
+            resolve_buffer,
+            readback_buffer,
+            period: queue.get_timestamp_period(),
+            copy_pending: false,
+            in_flight: false,
+            mapped: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    pub fn start(&self, encoder: &mut CommandEncoder) {
+        encoder.write_timestamp(&self.queries, 0);
+    }
+
+    pub fn end(&mut self, encoder: &mut CommandEncoder) {
+        encoder.write_timestamp(&self.queries, 1);
+
+        // the previous measurement is still being read, this one is just dropped then
+        if self.in_flight {
+            return;
+        }
+        encoder.resolve_query_set(&self.queries, 0..QUERY_COUNT, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            QUERY_BYTES,
+        );
+        self.copy_pending = true;
+    }
+
+    /// Has to be called after the encoder passed to [`Self::end`] was submitted.
+    pub fn submitted(&mut self) {
+        if !self.copy_pending {
+            return;
+        }
+        self.copy_pending = false;
+        self.in_flight = true;
+
+        let mapped = Arc::clone(&self.mapped);
+        self.readback_buffer
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                *mapped.lock().unwrap() = Some(result);
+            });
+    }
+
+    /// Returns the latest measurement, if one finished since the last call.
+    pub fn poll(&mut self, device: &Device) -> Option<Duration> {
+        if !self.in_flight {
+            return None;
+        }
+        device.poll(Maintain::Poll);
+        let result = self.mapped.lock().unwrap().take()?;
+        if let Err(err) = result {
+            // the buffer stays unmapped then, so the next frame can just try again
+            warn!("Could not read back GPU timestamps: {err}");
+            self.in_flight = false;
+            return None;
+        }
+
+        let timestamps: [u64; 2] = {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            bytemuck::pod_read_unaligned(&data)
+        };
+        self.readback_buffer.unmap();
+        self.in_flight = false;
+
+        let ticks = timestamps[1].saturating_sub(timestamps[0]);
+        Some(Duration::from_nanos(
+            (ticks as f64 * f64::from(self.period)) as u64,
+        ))
+    }
+}
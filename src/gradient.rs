@@ -0,0 +1,101 @@
+//! A two-color gradient covering the whole target, drawn instead of relying on the clear color.
+
+use anyhow::{bail, Context, Result};
+use wgpu::{
+    Color, ColorTargetState, ColorWrites, Device, FragmentState, MultisampleState,
+    PipelineLayoutDescriptor, PrimitiveState, RenderPass, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, TextureFormat, VertexState,
+};
+
+use crate::{globals::Globals, parse_clear_color};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Gradient {
+    pub start: Color,
+    pub end: Color,
+    /// Left to right if set, top to bottom otherwise.
+    pub horizontal: bool,
+}
+
+impl Gradient {
+    /// Expects `START END [DIRECTION]` as given to `--gradient`, an empty list means no gradient.
+    pub fn parse(values: &[String]) -> Result<Option<Self>> {
+        let (start, end, direction) = match values {
+            [] => return Ok(None),
+            [start, end] => (start, end, None),
+            [start, end, direction] => (start, end, Some(direction.as_str())),
+            _ => bail!("a gradient needs a start color, an end color and optionally a direction"),
+        };
+
+        let horizontal = match direction {
+            None | Some("vertical") => false,
+            Some("horizontal") => true,
+            Some(other) => {
+                bail!("`{other}` is not a direction, expected `horizontal` or `vertical`")
+            }
+        };
+        Ok(Some(Self {
+            start: parse_clear_color(start).context("invalid start color")?,
+            end: parse_clear_color(end).context("invalid end color")?,
+            horizontal,
+        }))
+    }
+}
+
+/// The colors themselves come from [`Globals`].
+pub struct GradientBackground {
+    pipeline: RenderPipeline,
+}
+
+impl GradientBackground {
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals: &Globals,
+    ) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("gradient shader"),
+            source: ShaderSource::Wgsl(include_str!("gradient.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("gradient pipeline layout"),
+            bind_group_layouts: &[&globals.layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("gradient pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: sample_count,
+                ..MultisampleState::default()
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+
+        Self { pipeline }
+    }
+
+    /// Expects the globals to be bound already.
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,38 @@
+struct Globals {
+    time: f32,
+    grid_size: f32,
+    resolution: vec2<f32>,
+    gradient_start: vec4<f32>,
+    gradient_end: vec4<f32>,
+    gradient_horizontal: u32,
+    zoom: f32,
+    pan: vec2<f32>,
+    view_projection: mat4x4<f32>,
+    pixel_projection: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // one triangle large enough to cover the whole screen, the parts outside are clipped
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // from left to right or from top to bottom
+    let t = select(in.uv.y, in.uv.x, globals.gradient_horizontal != 0u);
+    return mix(globals.gradient_start, globals.gradient_end, t);
+}
@@ -0,0 +1,113 @@
+//! Rendering a single frame into a texture without ever opening a window.
+
+use std::{iter, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use tracing::info;
+use wgpu::{
+    CommandEncoderDescriptor, Device, Extent3d, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureUsages, TextureView, TextureViewDescriptor,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    camera::Camera,
+    memory::{self, TrackedTexture},
+    readback,
+    renderer::{self, request_device},
+    scene::{self, Scene},
+    Args, DEFAULT_BACKGROUND_COLOR,
+};
+
+const FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// Renders one frame and writes it to `args.output`.
+pub fn run(args: &Args, size: PhysicalSize<u32>) -> Result<()> {
+    let pixels = render(args, size)?;
+    readback::save_png(&args.output, size.width, size.height, pixels)?;
+    info!("Saved headless frame to {}", args.output.display());
+
+    Ok(())
+}
+
+/// Sets up a device on its own and renders one frame with it, returned as tightly packed sRGB
+/// RGBA8 rows.
+pub fn render(args: &Args, size: PhysicalSize<u32>) -> Result<Vec<u8>> {
+    render_resized(args, &[size])
+}
+
+/// Like [`render`], but starts out at the first size and resizes the scene to all the others the
+/// same way a window being resized would, like when it's minimized to 0x0. There's no surface to
+/// configure, the target texture is just recreated instead. Only renders at the last one, which
+/// can't be 0x0 then.
+pub fn render_resized(args: &Args, sizes: &[PhysicalSize<u32>]) -> Result<Vec<u8>> {
+    let (&first, rest) = sizes
+        .split_first()
+        .context("Need at least one size to render at")?;
+
+    let instance = renderer::create_instance(args);
+    let adapter = renderer::request_adapter(&instance, args, None)?;
+    let format = scene::render_format(FORMAT, args);
+    let (device, queue) = request_device(&adapter, format, args)?;
+
+    // the target stands in for the surface, sized the same way a window's would be
+    let mut scene = Scene::new(&device, FORMAT, first, args)?;
+    let mut target =
+        renderer::fit_target_size(&device, first).map(|size| (size, create_target(&device, size)));
+    for &size in rest {
+        target = renderer::resize_scene(&mut scene, &device, &queue, size)
+            .map(|size| (size, create_target(&device, size)));
+    }
+    let Some((size, (texture, view))) = target else {
+        bail!("Cannot render into an empty texture");
+    };
+    if let Some(path) = &args.image {
+        scene.image_display.load(&device, &queue, path)?;
+    }
+    if args.hud {
+        let text = format!("Format: {FORMAT:?}\nSamples: {}", args.samples);
+        scene.hud.set_text(&device, &queue, Some(&text));
+    }
+    if args.camera {
+        // from where a window's camera starts out
+        let aspect = size.width as f32 / size.height.max(1) as f32;
+        scene
+            .globals
+            .set_view_projection(Camera::new(aspect).view_projection());
+    }
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+    scene.render(
+        &device,
+        &queue,
+        &mut encoder,
+        &view,
+        args.clear_color.unwrap_or(DEFAULT_BACKGROUND_COLOR),
+        Duration::ZERO,
+    );
+    queue.submit(iter::once(encoder.finish()));
+
+    readback::read_texture_rgba(&device, &queue, &texture)
+}
+
+fn create_target(device: &Device, size: PhysicalSize<u32>) -> (TrackedTexture, TextureView) {
+    let texture = memory::create_texture(
+        device,
+        &TextureDescriptor {
+            label: Some("headless target"),
+            size: Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        },
+    );
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    (texture, view)
+}
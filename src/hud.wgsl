@@ -0,0 +1,48 @@
+struct Globals {
+    time: f32,
+    grid_size: f32,
+    resolution: vec2<f32>,
+    gradient_start: vec4<f32>,
+    gradient_end: vec4<f32>,
+    gradient_horizontal: u32,
+    zoom: f32,
+    pan: vec2<f32>,
+    view_projection: mat4x4<f32>,
+    pixel_projection: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+@group(1) @binding(0)
+var text: texture_2d<f32>;
+
+// how many physical pixels each texel covers, along each axis
+const SCALE: f32 = 2.0;
+// distance from the top left corner of the window, in physical pixels
+const MARGIN: f32 = 8.0;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+    );
+
+    // in physical pixels from the top left corner, like the texture itself
+    let pixel = MARGIN + corners[index] * vec2<f32>(textureDimensions(text)) * SCALE;
+    let resolution = max(globals.resolution, vec2<f32>(1.0));
+    let ndc = vec2<f32>(pixel.x / resolution.x * 2.0 - 1.0, 1.0 - pixel.y / resolution.y * 2.0);
+    return vec4<f32>(ndc, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    // each texel covers SCALE×SCALE pixels, no filtering so the glyphs stay crisp
+    let texel = vec2<i32>((position.xy - MARGIN) / SCALE);
+    return textureLoad(text, texel, 0);
+}
@@ -0,0 +1,59 @@
+struct Globals {
+    time: f32,
+    grid_size: f32,
+    resolution: vec2<f32>,
+    gradient_start: vec4<f32>,
+    gradient_end: vec4<f32>,
+    gradient_horizontal: u32,
+    zoom: f32,
+    pan: vec2<f32>,
+    view_projection: mat4x4<f32>,
+    pixel_projection: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+@group(1) @binding(0)
+var image: texture_2d<f32>;
+@group(1) @binding(1)
+var image_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[index];
+
+    // shrink along whichever axis the image is relatively shorter in, so it keeps its aspect
+    let size = vec2<f32>(textureDimensions(image));
+    let image_aspect = size.x / size.y;
+    let window_aspect = globals.resolution.x / max(globals.resolution.y, 1.0);
+    let scale = select(
+        vec2<f32>(image_aspect / window_aspect, 1.0),
+        vec2<f32>(1.0, window_aspect / image_aspect),
+        image_aspect > window_aspect,
+    );
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(corner * scale * globals.zoom + globals.pan, 0.0, 1.0);
+    // texture coordinates have y pointing down
+    out.uv = vec2<f32>(corner.x * 0.5 + 0.5, 0.5 - corner.y * 0.5);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(image, image_sampler, in.uv);
+}
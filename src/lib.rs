@@ -0,0 +1,548 @@
+//! Everything that doesn't need a window, so it can be driven from tests as well as from the
+//! event loop in `main.rs`.
+
+pub mod blur;
+pub mod camera;
+pub mod canvas;
+pub mod config;
+pub mod depth;
+pub mod diagnostics;
+pub mod globals;
+pub mod gpu_timer;
+pub mod gradient;
+pub mod grid;
+pub mod headless;
+pub mod hud;
+pub mod image_display;
+pub mod memory;
+pub mod particles;
+pub mod readback;
+pub mod recording;
+pub mod rectangles;
+pub mod renderer;
+pub mod scene;
+pub mod stress;
+pub mod tone_mapping;
+pub mod touch;
+
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use gradient::Gradient;
+use renderer::LimitsPreset;
+use tone_mapping::Operator;
+use tracing::{info, warn};
+use wgpu::{Backends, Color, Features, PowerPreference, PresentMode};
+use winit::dpi::PhysicalSize;
+
+pub const DEFAULT_BACKGROUND_COLOR: Color = Color {
+    r: 0.05,
+    g: 0.05,
+    b: 0.05,
+    a: 1.0,
+};
+/// Same as [`DEFAULT_BACKGROUND_COLOR`] at half opacity, already premultiplied.
+pub const DEFAULT_TRANSPARENT_BACKGROUND_COLOR: Color = Color {
+    r: 0.025,
+    g: 0.025,
+    b: 0.025,
+    a: 0.5,
+};
+
+#[derive(Clone, Parser)]
+pub struct Args {
+    /// Color to clear the background with, either as hex (`1a1a2e`, optionally with alpha) or as
+    /// comma-separated floats in 0..=1 (`0.1,0.2,0.3,1.0`)
+    #[arg(long, env = "CLEAR_COLOR", value_parser = parse_clear_color)]
+    pub clear_color: Option<Color>,
+
+    /// Let the compositor show what's behind the window wherever the clear color isn't opaque,
+    /// the default clear color turns half transparent with this
+    #[arg(long)]
+    pub transparent: bool,
+
+    /// Open the windows without decorations like the title bar and borders
+    #[arg(long)]
+    pub borderless: bool,
+
+    /// Keep the windows above all others
+    #[arg(long)]
+    pub always_on_top: bool,
+
+    /// Open the windows maximized, whatever size was asked for only applies once they're restored
+    #[arg(long)]
+    pub maximized: bool,
+
+    /// How many samples per pixel to render with, 1 disables MSAA
+    #[arg(long, default_value_t = 1, value_parser = parse_sample_count)]
+    pub samples: u32,
+
+    /// Present mode to use instead of picking the lowest-latency one available, one of `fifo`,
+    /// `fifo-relaxed`, `mailbox`, `immediate`, `auto-vsync` or `auto-no-vsync`
+    #[arg(long, value_parser = parse_present_mode)]
+    pub present_mode: Option<PresentMode>,
+
+    /// Save power while no window is focused by switching to `fifo`, which waits for vsync, and
+    /// back to the chosen present mode once one is focused again
+    #[arg(long)]
+    pub vsync_off_when_unfocused: bool,
+
+    /// Which kind of adapter to ask for, `low` or `high`. Taken from `WGPU_POWER_PREF` if not
+    /// given, like the wgpu examples do
+    #[arg(long, value_parser = parse_power_preference)]
+    pub power: Option<PowerPreference>,
+
+    /// Only accept a fallback (usually software) adapter
+    #[arg(long)]
+    pub force_fallback: bool,
+
+    /// Comma-separated wgpu features to enable on top of the ones used anyway, like
+    /// `TIMESTAMP_QUERY,POLYGON_MODE_LINE`. Whatever the adapter doesn't support is left out with a
+    /// warning
+    #[arg(long, value_parser = parse_features)]
+    pub features: Option<Features>,
+
+    /// Which limits to request the device with, one of `webgl2`, `downlevel`, `default` or
+    /// `adapter` for everything the adapter can do. Fails if the adapter can't meet them. Without
+    /// this, the WebGL2 limits are used, with the compute ones of `downlevel` if available
+    #[arg(long, value_parser = parse_limits)]
+    pub limits: Option<LimitsPreset>,
+
+    /// Catch API misuse with the backend's validation layers. wgpu 0.16 only turns them on in
+    /// debug builds, so in release builds this can only ask the Vulkan loader for them
+    #[arg(long)]
+    pub validation: bool,
+
+    /// Print all adapters wgpu can find before picking one
+    #[arg(long)]
+    pub list_adapters: bool,
+
+    /// Print everything the surface supports once the adapter is picked
+    #[arg(long)]
+    pub print_caps: bool,
+
+    /// Print adapter, surface, device and version info as one JSON object on startup
+    #[arg(long)]
+    pub diagnostics_json: bool,
+
+    /// Exit right after startup instead of running the event loop, useful with
+    /// --diagnostics-json
+    #[arg(long)]
+    pub exit: bool,
+
+    /// Only use this backend, one of `vulkan`, `dx12`, `metal` or `gl`. Taken from `WGPU_BACKEND`
+    /// if not given, which can also be a comma-separated list of backends
+    #[arg(long, value_parser = parse_backend)]
+    pub backend: Option<Backends>,
+
+    /// Render continuously and sweep the clear color through all hues, instead of only redrawing
+    /// when the OS asks
+    #[arg(long)]
+    pub animate: bool,
+
+    /// Map the cursor position to the red and green channels of the clear color, to check that
+    /// cursor coordinates and scaling are right. Can be toggled with C
+    #[arg(long)]
+    pub cursor_color: bool,
+
+    /// Only render a frame whenever the right arrow or space is pressed, for stepping through
+    /// frames one by one
+    #[arg(long)]
+    pub step: bool,
+
+    /// Saturation of the animated clear color, in 0..=1
+    #[arg(long, default_value_t = 0.6, value_parser = parse_unit_interval)]
+    pub saturation: f64,
+
+    /// Value (brightness) of the animated clear color, in 0..=1
+    #[arg(long, default_value_t = 0.5, value_parser = parse_unit_interval)]
+    pub value: f64,
+
+    /// Don't open a window, just render one frame of this size (`WIDTHxHEIGHT`) into --output
+    #[arg(long, value_parser = parse_size)]
+    pub headless: Option<PhysicalSize<u32>>,
+
+    /// How many previous positions of each finger to draw as a fading trail
+    #[arg(long, default_value_t = 64)]
+    pub trail_length: usize,
+
+    /// How many windows to open, they all share the same device
+    #[arg(long, default_value_t = 1, value_parser = parse_window_count)]
+    pub windows: usize,
+
+    /// Open the windows on the monitor with this index, counting from 0 in the order the platform
+    /// lists them. Falls back to the primary monitor if there's no such monitor
+    #[arg(long)]
+    pub monitor: Option<usize>,
+
+    /// Draw a gradient from START to END behind everything else, both colors in the same format
+    /// as --clear-color. DIRECTION is `vertical` (the default) or `horizontal`
+    #[arg(
+        long = "gradient",
+        num_args = 2..=3,
+        value_names = ["START", "END", "DIRECTION"]
+    )]
+    pub gradient_args: Vec<String>,
+
+    /// Draw a checkerboard behind everything else, with cells this many physical pixels wide
+    #[arg(long, value_parser = parse_grid_size)]
+    pub grid_size: Option<u32>,
+
+    /// WGSL file to draw the triangle with instead of the built-in shader, reloaded whenever it
+    /// changes. Needs the same `vs_main` and `fs_main` entry points and globals
+    #[arg(long)]
+    pub shader: Option<PathBuf>,
+
+    /// Leave out the triangle, to only look at the background and anything else enabled
+    #[arg(long)]
+    pub no_triangle: bool,
+
+    /// Show the HUD from the start, it can still be toggled with H. Headless, it only shows the
+    /// format and sample count, since there's nothing else to measure
+    #[arg(long)]
+    pub hud: bool,
+
+    /// Draw two overlapping triangles at different depths instead of the usual one, to check
+    /// that depth testing works
+    #[arg(long)]
+    pub depth: bool,
+
+    /// Draw a small world to fly through instead of the triangle. WASD move instead of what these
+    /// keys usually do, and the mouse looks around while G has grabbed the cursor
+    #[arg(long)]
+    pub camera: bool,
+
+    /// Draw a few rectangles at fixed pixel positions from the top left corner, which should stay
+    /// put when resizing
+    #[arg(long)]
+    pub rectangles: bool,
+
+    /// Paint with the fingers, every stroke stays on a canvas over everything else
+    #[arg(long)]
+    pub paint: bool,
+
+    /// Spawn a burst of particles falling under gravity wherever a finger touches down. Needs
+    /// compute shaders, is left out without them
+    #[arg(long)]
+    pub particles: bool,
+
+    /// Draw this many small quads bouncing around in a single instanced draw call, to see how
+    /// rendering scales with the load. Renders continuously and logs the frame rate
+    #[arg(long, value_parser = parse_stress_count)]
+    pub stress: Option<u32>,
+
+    /// Blur everything with a Gaussian blur of this radius in physical pixels, 0 disables it. `[`
+    /// and `]` change it while running
+    #[arg(long, default_value_t = 0, value_parser = parse_blur_radius)]
+    pub blur: u32,
+
+    /// Render everything in linear floating point first and map it into the window's format with
+    /// this operator, one of `none`, `reinhard` or `aces`. T switches between them while running
+    #[arg(long, value_parser = parse_tone_mapping)]
+    pub tone_mapping: Option<Operator>,
+
+    /// Image to show letterboxed over the whole window
+    #[arg(long)]
+    pub image: Option<PathBuf>,
+
+    /// Where to write the frame rendered in --headless mode
+    #[arg(long, default_value = "headless.png")]
+    pub output: PathBuf,
+
+    /// Sleep after presenting to stay below this many frames per second, 0 means uncapped
+    #[arg(long)]
+    pub max_fps: Option<u32>,
+
+    /// Exit cleanly after running for this long, like `5s` or `500ms`
+    #[arg(long, value_parser = parse_duration)]
+    pub run_for: Option<Duration>,
+
+    /// Render exactly this many frames as fast as possible, print statistics about how long they
+    /// took on the CPU and exit. Uses the `immediate` present mode if there is one and
+    /// --present-mode isn't given
+    #[arg(long, value_parser = parse_frame_count)]
+    pub frames: Option<usize>,
+
+    /// Exit with an error if no frame was presented for this long, like `5s`. Only counts while
+    /// frames are rendered continuously, like with --animate, and not while paused, stepping,
+    /// suspended, or while no window is focused or all are minimized
+    #[arg(long, value_parser = parse_duration)]
+    pub watchdog: Option<Duration>,
+
+    /// Warn about frames that took longer than this many milliseconds on the CPU
+    #[arg(long, default_value_t = 16.7, value_parser = parse_frame_budget)]
+    pub frame_budget_ms: f64,
+
+    /// Change the clear color from another thread after two seconds and take a screenshot, to show
+    /// off user events
+    #[arg(long)]
+    pub demo_user_events: bool,
+
+    /// Write touch, keyboard, mouse and resize events to this JSONL file as they happen
+    #[arg(long)]
+    pub record: Option<PathBuf>,
+
+    /// Feed the events from a file written by --record through the app again, with the same
+    /// timing
+    #[arg(long)]
+    pub replay: Option<PathBuf>,
+
+    /// TOML file to read defaults for the other options from, it's fine if it doesn't exist
+    #[arg(long, default_value = "config.toml")]
+    pub config: PathBuf,
+
+    /// Inner width of the windows in physical pixels, needs --height as well
+    #[arg(long, requires = "height", value_parser = parse_dimension)]
+    pub width: Option<u32>,
+
+    /// Inner height of the windows in physical pixels, needs --width as well
+    #[arg(long, requires = "width", value_parser = parse_dimension)]
+    pub height: Option<u32>,
+
+    /// Title of the windows, the adapter and frame times are appended to it
+    #[arg(long)]
+    pub title: Option<String>,
+
+    /// From --width and --height or the config file, winit picks one if it's neither.
+    #[arg(skip)]
+    pub size: Option<PhysicalSize<u32>>,
+    /// Parsed from `gradient_args`, clap can't do that on its own with the mixed value types.
+    #[arg(skip)]
+    pub gradient: Option<Gradient>,
+}
+
+impl Args {
+    /// Fills in the backend and power preference from `WGPU_BACKEND` and `WGPU_POWER_PREF`
+    /// wherever they weren't given as arguments, the same way wgpu's own tools read them.
+    pub fn apply_wgpu_env(&mut self) {
+        if self.backend.is_none() {
+            match wgpu::util::backend_bits_from_env() {
+                Some(backends) if backends.is_empty() => {
+                    warn!("WGPU_BACKEND names no known backend, ignoring it");
+                }
+                Some(backends) => {
+                    info!("Using backends {backends:?} from WGPU_BACKEND");
+                    self.backend = Some(backends);
+                }
+                None => (),
+            }
+        }
+        if self.power.is_none() {
+            if let Some(power) = wgpu::util::power_preference_from_env() {
+                info!("Using power preference {power:?} from WGPU_POWER_PREF");
+                self.power = Some(power);
+            }
+        }
+    }
+}
+
+fn parse_size(input: &str) -> Result<PhysicalSize<u32>> {
+    let (width, height) = input
+        .split_once('x')
+        .with_context(|| format!("`{input}` is not a size, expected `WIDTHxHEIGHT`"))?;
+    let width = width.parse().context("width must be a number")?;
+    let height = height.parse().context("height must be a number")?;
+    if width == 0 || height == 0 {
+        bail!("width and height must both be positive");
+    }
+    Ok(PhysicalSize::new(width, height))
+}
+
+/// Accepts whole or fractional numbers with either an `ms` or `s` suffix, like `500ms` or `1.5s`.
+fn parse_duration(input: &str) -> Result<Duration> {
+    let (number, unit_secs) = if let Some(number) = input.strip_suffix("ms") {
+        (number, 0.001)
+    } else if let Some(number) = input.strip_suffix('s') {
+        (number, 1.0)
+    } else {
+        bail!("`{input}` has no unit, expected something like `5s` or `500ms`");
+    };
+    let number: f64 = number
+        .trim()
+        .parse()
+        .with_context(|| format!("`{number}` is not a number"))?;
+    Duration::try_from_secs_f64(number * unit_secs)
+        .with_context(|| format!("`{input}` is not a valid duration"))
+}
+
+fn parse_frame_budget(input: &str) -> Result<f64> {
+    let budget: f64 = input.parse().context("frame budget must be a number")?;
+    if !(budget > 0.0 && budget.is_finite()) {
+        bail!("frame budget must be positive, got {budget}");
+    }
+    Ok(budget)
+}
+
+fn parse_backend(input: &str) -> Result<Backends> {
+    Ok(match input {
+        "vulkan" => Backends::VULKAN,
+        "dx12" => Backends::DX12,
+        "metal" => Backends::METAL,
+        "gl" => Backends::GL,
+        _ => bail!("unknown backend `{input}`, expected `vulkan`, `dx12`, `metal` or `gl`"),
+    })
+}
+
+fn parse_power_preference(input: &str) -> Result<PowerPreference> {
+    Ok(match input {
+        "low" => PowerPreference::LowPower,
+        "high" => PowerPreference::HighPerformance,
+        _ => bail!("unknown power preference `{input}`, expected `low` or `high`"),
+    })
+}
+
+fn parse_present_mode(input: &str) -> Result<PresentMode> {
+    Ok(match input {
+        "fifo" => PresentMode::Fifo,
+        "fifo-relaxed" => PresentMode::FifoRelaxed,
+        "mailbox" => PresentMode::Mailbox,
+        "immediate" => PresentMode::Immediate,
+        "auto-vsync" => PresentMode::AutoVsync,
+        "auto-no-vsync" => PresentMode::AutoNoVsync,
+        _ => bail!("unknown present mode `{input}`"),
+    })
+}
+
+fn parse_limits(input: &str) -> Result<LimitsPreset> {
+    Ok(match input {
+        "webgl2" => LimitsPreset::Webgl2,
+        "downlevel" => LimitsPreset::Downlevel,
+        "default" => LimitsPreset::Default,
+        "adapter" => LimitsPreset::Adapter,
+        _ => bail!(
+            "unknown limits preset `{input}`, expected `webgl2`, `downlevel`, `default` or \
+            `adapter`"
+        ),
+    })
+}
+
+fn parse_tone_mapping(input: &str) -> Result<Operator> {
+    Ok(match input {
+        "none" => Operator::None,
+        "reinhard" => Operator::Reinhard,
+        "aces" => Operator::Aces,
+        _ => bail!("unknown tone mapping operator `{input}`"),
+    })
+}
+
+/// Accepts the names wgpu uses, case doesn't matter and `-` works in place of `_`.
+fn parse_features(input: &str) -> Result<Features> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .try_fold(Features::empty(), |features, name| {
+            let feature = Features::from_name(&name.to_uppercase().replace('-', "_"))
+                .with_context(|| format!("unknown feature `{name}`"))?;
+            Ok(features | feature)
+        })
+}
+
+fn parse_sample_count(input: &str) -> Result<u32> {
+    let count = input.parse().context("sample count must be a number")?;
+    if !matches!(count, 1 | 2 | 4 | 8) {
+        bail!("sample count must be one of 1, 2, 4 or 8, got {count}");
+    }
+    Ok(count)
+}
+
+fn parse_window_count(input: &str) -> Result<usize> {
+    let count = input.parse().context("window count must be a number")?;
+    if count == 0 {
+        bail!("need at least one window");
+    }
+    Ok(count)
+}
+
+fn parse_frame_count(input: &str) -> Result<usize> {
+    let count = input.parse().context("frame count must be a number")?;
+    if count == 0 {
+        bail!("need at least one frame");
+    }
+    Ok(count)
+}
+
+fn parse_dimension(input: &str) -> Result<u32> {
+    let dimension = input.parse().context("must be a number")?;
+    if dimension == 0 {
+        bail!("must be positive");
+    }
+    Ok(dimension)
+}
+
+fn parse_grid_size(input: &str) -> Result<u32> {
+    let size = input.parse().context("grid size must be a number")?;
+    if size == 0 {
+        bail!("grid size must be positive");
+    }
+    Ok(size)
+}
+
+fn parse_stress_count(input: &str) -> Result<u32> {
+    let count = input.parse().context("quad count must be a number")?;
+    if count == 0 {
+        bail!("need at least one quad");
+    }
+    Ok(count)
+}
+
+fn parse_blur_radius(input: &str) -> Result<u32> {
+    let radius = input.parse().context("blur radius must be a number")?;
+    if radius > blur::MAX_RADIUS {
+        bail!("blur radius can be at most {}", blur::MAX_RADIUS);
+    }
+    Ok(radius)
+}
+
+fn parse_unit_interval(input: &str) -> Result<f64> {
+    let number: f64 = input.parse().context("must be a number")?;
+    if !(0.0..=1.0).contains(&number) {
+        bail!("must be in 0..=1, got {number}");
+    }
+    Ok(number)
+}
+
+/// Parses either a hex color like `1a1a2e`/`#1a1a2eff` or comma-separated floats like
+/// `0.1,0.2,0.3` or `0.1,0.2,0.3,1.0`. Alpha defaults to 1 if left out.
+pub fn parse_clear_color(input: &str) -> Result<Color> {
+    let input = input.trim();
+
+    let components = if input.contains(',') {
+        input
+            .split(',')
+            .map(|component| {
+                let component = component.trim();
+                let value: f64 = component
+                    .parse()
+                    .with_context(|| format!("`{component}` is not a number"))?;
+                if !(0.0..=1.0).contains(&value) {
+                    bail!("color component `{component}` is out of range, expected 0..=1");
+                }
+                Ok(value)
+            })
+            .collect::<Result<Vec<_>>>()?
+    } else {
+        let hex = input.strip_prefix('#').unwrap_or(input);
+        if !matches!(hex.len(), 6 | 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!("`{input}` is not a hex color, expected 6 or 8 hex digits like `1a1a2e`");
+        }
+
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                let channel = u8::from_str_radix(&hex[i..i + 2], 16)?;
+                Ok(f64::from(channel) / 255.0)
+            })
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    match components[..] {
+        [r, g, b] => Ok(Color { r, g, b, a: 1.0 }),
+        [r, g, b, a] => Ok(Color { r, g, b, a }),
+        _ => bail!(
+            "expected 3 or 4 color components, got {} in `{input}`",
+            components.len()
+        ),
+    }
+}
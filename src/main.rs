@@ -1,52 +1,187 @@
-use std::iter;
-
-use anyhow::{Context, Result};
-use pollster::FutureExt;
-use wgpu::{
-    Adapter, CommandEncoderDescriptor, CompositeAlphaMode, Device, DeviceDescriptor, Features,
-    Instance, InstanceDescriptor, Limits, LoadOp, Operations, PresentMode, Queue,
-    RenderPassColorAttachment, RenderPassDescriptor, RequestAdapterOptions, Surface,
-    SurfaceConfiguration, TextureUsages, TextureViewDescriptor,
+mod shader_watch;
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
+    marker::PhantomData,
+    mem,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
 };
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use glam::Vec3;
+use image::ImageFormat;
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::EnvFilter;
+use wgpu::{Color, Features, PowerPreference, PresentMode};
 use winit::{
-    event::{Event, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
-    window::Window,
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{
+        DeviceEvent, ElementState, Event, Ime, InnerSizeWriter, MouseButton, MouseScrollDelta,
+        StartCause, TouchPhase, WindowEvent,
+    },
+    event_loop::{ControlFlow, EventLoop, EventLoopBuilder, EventLoopProxy},
+    keyboard::{KeyCode, ModifiersState},
+    monitor::MonitorHandle,
+    window::{CursorGrabMode, Fullscreen, Window, WindowBuilder, WindowId, WindowLevel},
+};
+use winit_testing_grounds::{
+    blur,
+    camera::Camera,
+    config::Config,
+    globals::View,
+    gradient::Gradient,
+    headless, memory, parse_clear_color,
+    recording::{RecordedEvent, Recorder, Replayer},
+    renderer::{is_zero_sized, RenderError, Renderer},
+    Args, DEFAULT_BACKGROUND_COLOR, DEFAULT_TRANSPARENT_BACKGROUND_COLOR,
 };
 
 fn main() {
-    if let Err(err) = run() {
-        eprintln!("{err}");
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| EnvFilter::new("warn,winit_testing_grounds=info")),
+        )
+        .init();
+
+    // as early as possible, to see how long everything takes until there's something on screen
+    let launch = Instant::now();
+    if let Err(err) = run(launch) {
+        error!("{err:#}");
         std::process::exit(1);
     }
 }
 
-fn run() -> Result<()> {
-    let (event_loop, mut state) = State::new()?;
+fn run(launch: Instant) -> Result<()> {
+    let mut args = Args::parse();
+    // clap makes sure they're only ever given together
+    args.size = args
+        .width
+        .zip(args.height)
+        .map(|(width, height)| PhysicalSize::new(width, height));
+    // arguments win over the environment, which wins over the config file
+    args.apply_wgpu_env();
+    Config::load(&args.config)?.apply_to(&mut args)?;
+    args.gradient = Gradient::parse(&args.gradient_args).context("Invalid --gradient")?;
+    if let Some(size) = args.headless {
+        return headless::run(&args, size);
+    }
+
+    let (event_loop, state) = State::new(&args, launch)?;
+    if args.diagnostics_json {
+        if let Some(renderer) = &state.renderer {
+            println!("{}", serde_json::to_string(&renderer.diagnostics())?);
+        }
+    }
+    if args.exit {
+        return Ok(());
+    }
+    if args.demo_user_events {
+        spawn_demo_thread(event_loop.create_proxy());
+    }
+    // has to stay around for as long as the event loop runs
+    let _shader_watcher = args
+        .shader
+        .as_deref()
+        .map(|path| shader_watch::watch(path, event_loop.create_proxy()))
+        .transpose()?;
+    run_event_loop(event_loop, state)
+}
 
+/// Can be sent from any thread through an [`EventLoopProxy`] to drive the window from outside.
+#[derive(Debug)]
+enum UserEvent {
+    SetClearColor(Color),
+    Screenshot,
+    /// The file given to `--shader` was written to.
+    ShaderChanged,
+}
+
+fn spawn_demo_thread(proxy: EventLoopProxy<UserEvent>) {
+    thread::spawn(move || {
+        thread::sleep(Duration::from_secs(2));
+        let color = Color {
+            r: 0.1,
+            g: 0.2,
+            b: 0.5,
+            a: 1.0,
+        };
+        // the window might have been closed already, nobody to tell then
+        if proxy.send_event(UserEvent::SetClearColor(color)).is_err() {
+            return;
+        }
+
+        // give the new color a moment to actually show up
+        thread::sleep(Duration::from_millis(500));
+        let _ = proxy.send_event(UserEvent::Screenshot);
+    });
+}
+
+/// Drives `state` until any of the windows is closed.
+fn run_event_loop(event_loop: EventLoop<UserEvent>, mut state: State<UserEvent>) -> Result<()> {
     event_loop.run(move |event, _, flow| {
         let result = match event {
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::Touch(touch) => {
-                    dbg!(touch.phase, touch.location);
-                    Ok(())
-                }
-                WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
-                    state.reconfigure_surface();
-                    Ok(())
-                }
-                WindowEvent::CloseRequested => {
-                    flow.set_exit();
-                    Ok(())
-                }
-                _ => Ok(()),
+            Event::WindowEvent { window_id, event } => match state.window_index(window_id) {
+                Some(index) => state.handle_window_event(index, event, flow),
+                None => Ok(()),
+            },
+            Event::AboutToWait => {
+                // the ones that weren't redrawn, like unfocused windows, still need the new size
+                let result = state
+                    .apply_pending_resizes()
+                    .context("Could not reconfigure surface")
+                    .and_then(|()| state.replay_due_events(flow))
+                    .and_then(|()| state.check_watchdog());
+                state.update_control_flow(flow);
+                result
+            }
+            Event::NewEvents(StartCause::ResumeTimeReached { .. }) => {
+                state.request_redraw_all();
+                Ok(())
+            }
+            Event::RedrawRequested(_) if state.is_time_up() => {
+                flow.set_exit();
+                Ok(())
+            }
+            Event::UserEvent(event) => {
+                state.user_event(event);
+                Ok(())
+            }
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } => {
+                state.mouse_motion(delta);
+                Ok(())
+            }
+            Event::Suspended => {
+                state.suspend();
+                Ok(())
+            }
+            Event::Resumed => state.resume().context("Could not recreate surfaces"),
+            Event::RedrawRequested(window_id) => match state.window_index(window_id) {
+                Some(index) => state
+                    .draw(index)
+                    .or_else(|err| state.recover(index, err))
+                    .context("Could not draw next frame"),
+                None => Ok(()),
             },
-            Event::RedrawRequested(_) => state.draw().context("Could not draw next frame"),
+            Event::LoopExiting => {
+                state.frame_histogram.print();
+                if let Some(benchmark) = &state.benchmark {
+                    benchmark.print();
+                }
+                Ok(())
+            }
             _ => Ok(()),
         };
 
         if let Err(err) = result {
-            eprintln!("{err}");
+            error!("{err:#}");
             *flow = ControlFlow::ExitWithCode(1);
         }
     })?;
@@ -54,120 +189,1729 @@ fn run() -> Result<()> {
     Ok(())
 }
 
-struct State {
-    adapter: Adapter,
-    device: Device,
-    queue: Queue,
-    surface: Surface,
+struct State<T: 'static> {
+    // has to be dropped before the windows, as its surfaces refer to them. Only missing while
+    // it's being recreated after the device was lost.
+    renderer: Option<Renderer>,
+    /// What was picked through the arguments or V, restored once a window is focused again.
+    /// `None` while there's no renderer.
+    desired_present_mode: Option<PresentMode>,
+    /// What the surfaces actually use, only differs from the desired one while saving power.
+    effective_present_mode: Option<PresentMode>,
+    /// The surfaces are gone in the meantime, so there's nothing to draw to.
+    suspended: bool,
+    /// Of the adapter the renderer is on, for the window titles.
+    adapter_name: String,
+    /// What the device of the renderer was created with, so optional features like wireframes can
+    /// check whether they're there.
+    features: Features,
+    /// In the same order as the renderer knows their surfaces.
+    windows: Vec<WindowState>,
+    /// Needed again for recreating the renderer.
+    args: Args,
+    last_recreation_attempt: Instant,
+
+    background_color: Color,
+    /// Which modifiers are held right now, across all windows.
+    modifiers: ModifiersState,
+
+    animate: bool,
+    /// Toggled with C, overrides the red and green channels of the clear color with where the
+    /// cursor is in each window.
+    cursor_color: bool,
+    /// Whether frames are only rendered when asked for by a keypress.
+    step: bool,
+    /// Toggled with space, nothing is rendered while set.
+    paused: bool,
+    /// Toggled with H, applies to all windows.
+    hud_visible: bool,
+    /// Physical keys held down right now, for moving the camera.
+    held_keys: HashSet<KeyCode>,
+    /// CPU time of every frame in any window, printed on exit.
+    frame_histogram: FrameHistogram,
+    frame_budget: FrameBudget,
+    /// Only there with --frames.
+    benchmark: Option<Benchmark>,
+    start: Instant,
+    /// How long to run before exiting on our own, if at all.
+    run_for: Option<Duration>,
+    /// Minimum time between two presented frames, if capped.
+    frame_interval: Option<Duration>,
+    /// When the previous frame was presented, after any sleeping for the frame limit.
+    last_frame: Instant,
+    /// How long [`Self::last_frame`] may be in the past before giving up.
+    watchdog: Option<Duration>,
+    /// When frames were last skipped on purpose, the watchdog only counts from here if that's
+    /// after [`Self::last_frame`].
+    watchdog_start: Instant,
+
+    /// When `main` was entered, until the time to the first frame was reported.
+    launch: Option<Instant>,
+
+    /// How many positions to keep in each trail, besides the current one.
+    trail_length: usize,
+
+    // both only ever see the first window, so the recording can be replayed with any count
+    recorder: Option<Recorder>,
+    replayer: Option<Replayer>,
+    /// The most recent interesting events, the oldest first, for dumping with L.
+    event_log: VecDeque<LoggedEvent>,
+
+    user_event: PhantomData<fn(T)>,
+}
+
+/// One entry of [`State::event_log`].
+struct LoggedEvent {
+    time: Instant,
+    window: usize,
+    description: String,
+}
 
+/// Everything that exists once per window.
+struct WindowState {
     window: Window,
+    /// What the window is called, before the adapter name and any FPS stats are appended.
+    title: String,
+    frame_times: FrameTimes,
+    /// Nothing is rendered into the window while it's in the background.
+    focused: bool,
+    /// Last known position of the cursor inside the window, if it was ever in there.
+    cursor: Option<PhysicalPosition<f64>>,
+    view: View,
+    /// Whether the left mouse button is held down, moving the view along with the cursor.
+    dragging: bool,
+    /// In step mode, whether the next redraw is allowed to actually render something.
+    step_pending: bool,
+    /// Where each finger currently on the window has been recently, keyed by touch ID. The last
+    /// position is where it is right now.
+    touches: HashMap<u64, VecDeque<PhysicalPosition<f64>>>,
+    /// Whether a file is dragged over the window right now, but not dropped yet.
+    file_hovering: bool,
+    /// The most recent frame time averages, for the HUD.
+    frame_report: Option<FrameReport>,
+    /// Only used with `--camera`.
+    camera: Camera,
+    last_camera_update: Instant,
+    /// Whether the cursor is locked to the window for looking around.
+    cursor_grabbed: bool,
+    /// Raw mouse motion since the last frame, unaccelerated. Only collected while the cursor is
+    /// grabbed, and turned into looking around once per frame.
+    pending_look: (f64, f64),
+    /// The one the window was at last we know of, since it might already report the new one
+    /// while the scale factor changes.
+    scale_factor: f64,
+    /// Whether the window was resized since the surface was last configured. Dragging an edge
+    /// sends lots of `Resized` events, this way they only lead to one reconfiguration per frame.
+    resize_pending: bool,
 }
 
-impl State {
-    fn new() -> Result<(EventLoop<()>, Self)> {
-        let event_loop = EventLoop::new()?;
-        let window = Window::new(&event_loop)?;
+impl<T: 'static> State<T> {
+    fn new(args: &Args, launch: Instant) -> Result<(EventLoop<T>, Self)> {
+        let event_loop = EventLoopBuilder::with_user_event().build()?;
+        let monitor = args
+            .monitor
+            .and_then(|index| choose_monitor(&event_loop, index));
+        let base_title = args
+            .title
+            .clone()
+            .unwrap_or_else(|| env!("CARGO_PKG_NAME").to_string());
+        let windows = (0..args.windows)
+            .map(|i| {
+                let title = if args.windows == 1 {
+                    base_title.clone()
+                } else {
+                    format!("{base_title} ({}/{})", i + 1, args.windows)
+                };
+                let level = if args.always_on_top {
+                    WindowLevel::AlwaysOnTop
+                } else {
+                    WindowLevel::Normal
+                };
+                let mut window = WindowBuilder::new()
+                    .with_title(&title)
+                    .with_transparent(args.transparent)
+                    .with_decorations(!args.borderless)
+                    .with_window_level(level)
+                    .with_maximized(args.maximized);
+                if let Some(size) = args.size {
+                    window = window.with_inner_size(size);
+                }
+                if let Some(monitor) = &monitor {
+                    let origin = monitor.position();
+                    window = window.with_position(PhysicalPosition::new(
+                        origin.x + MONITOR_OFFSET,
+                        origin.y + MONITOR_OFFSET,
+                    ));
+                }
 
-        let instance = Instance::new(InstanceDescriptor::default());
-        // SAFETY: window was just created and is dropped after the surface due to State's drop
-        // order
-        let surface = unsafe { instance.create_surface(&window) }?;
+                // the surface is configured with whatever size the window actually ended up with,
+                // and window managers that maximize only later send a `Resized` once they do
+                let window = window.build(&event_loop)?;
+                // off by default on most platforms
+                window.set_ime_allowed(true);
+                let camera = Camera::new(aspect(window.inner_size()));
+                let scale_factor = window.scale_factor();
 
-        let adapter = instance
-            .request_adapter(&RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::LowPower,
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
+                Ok(WindowState {
+                    window,
+                    title,
+                    frame_times: FrameTimes::new(),
+                    focused: true,
+                    cursor: None,
+                    view: View::default(),
+                    dragging: false,
+                    step_pending: false,
+                    touches: HashMap::new(),
+                    file_hovering: false,
+                    frame_report: None,
+                    camera,
+                    last_camera_update: Instant::now(),
+                    cursor_grabbed: false,
+                    pending_look: (0.0, 0.0),
+                    scale_factor,
+                    resize_pending: false,
+                })
             })
-            .block_on()
-            .context("Found no appropiate adapter")?;
-
-        let (device, queue) = adapter
-            .request_device(
-                &DeviceDescriptor {
-                    label: None,
-                    features: Features::empty(),
-                    limits: Limits::downlevel_webgl2_defaults(),
-                },
-                None,
-            )
-            .block_on()
-            .context("Found no appropiate device")?;
-
-        configure_surface(&surface, &device, &adapter, &window);
-
-        Ok((
-            event_loop,
-            State {
-                adapter,
-                device,
-                queue,
-                surface,
-                window,
-            },
-        ))
+            .collect::<Result<Vec<_>>>()?;
+
+        // SAFETY: the windows are moved into State right after and dropped after the renderer
+        // due to State's drop order
+        let renderer = unsafe { Renderer::new(args, &window_refs(&windows)) }?;
+
+        let recorder = args.record.as_deref().map(Recorder::create).transpose()?;
+        let replayer = args.replay.as_deref().map(Replayer::open).transpose()?;
+
+        let mut state = State {
+            adapter_name: renderer.adapter_name(),
+            features: renderer.features(),
+            desired_present_mode: Some(renderer.present_mode()),
+            effective_present_mode: Some(renderer.present_mode()),
+            renderer: Some(renderer),
+            suspended: false,
+            windows,
+            args: args.clone(),
+            last_recreation_attempt: Instant::now(),
+            modifiers: ModifiersState::empty(),
+            background_color: args
+                .clear_color
+                .unwrap_or_else(|| default_background_color(args)),
+            trail_length: args.trail_length,
+            animate: args.animate,
+            cursor_color: args.cursor_color,
+            step: args.step,
+            paused: false,
+            hud_visible: args.hud,
+            frame_histogram: FrameHistogram::new(),
+            frame_budget: FrameBudget::new(Duration::from_secs_f64(args.frame_budget_ms / 1000.0)),
+            benchmark: args.frames.map(Benchmark::new),
+            held_keys: HashSet::new(),
+            start: Instant::now(),
+            run_for: args.run_for,
+            frame_interval: args
+                .max_fps
+                .filter(|&fps| fps != 0)
+                .map(|fps| Duration::from_secs_f64(1.0 / f64::from(fps))),
+            last_frame: Instant::now(),
+            watchdog: args.watchdog,
+            watchdog_start: Instant::now(),
+            launch: Some(launch),
+            recorder,
+            replayer,
+            event_log: VecDeque::with_capacity(EVENT_LOG_LENGTH),
+            user_event: PhantomData,
+        };
+        state.reset_titles();
+        state.set_hud_texts();
+        Ok((event_loop, state))
+    }
+
+    /// What window `index` is called, before any FPS stats are appended. Also shows where the
+    /// cursor is and the size of the surface, to compare the coordinates winit reports with.
+    fn title(&self, index: usize) -> String {
+        let window = &self.windows[index];
+        let cursor = match window.cursor {
+            Some(cursor) => format!("cursor {:.1}, {:.1}", cursor.x, cursor.y),
+            None => "cursor outside".to_string(),
+        };
+        let surface = match &self.renderer {
+            Some(renderer) => {
+                let size = renderer.surface_size(index);
+                format!("surface {}x{}", size.width, size.height)
+            }
+            None => "no surface".to_string(),
+        };
+        format!(
+            "{} — {} — {cursor} — {surface}",
+            window.title, self.adapter_name
+        )
+    }
+
+    /// Like [`Self::title`], with the last FPS stats if there are any.
+    fn update_title(&self, index: usize) {
+        let title = self.title(index);
+        let window = &self.windows[index];
+        let title = match &window.frame_report {
+            Some(report) => {
+                let interval = report.interval.as_secs_f64();
+                format!(
+                    "{title} — {:.1} FPS ({:.1}ms) — {}",
+                    1.0 / interval,
+                    interval * 1000.0,
+                    report.cpu_and_gpu(),
+                )
+            }
+            None => title,
+        };
+        window.window.set_title(&title);
+    }
+
+    /// Gets rid of any stats in the titles, or outdated adapter names.
+    fn reset_titles(&self) {
+        for (index, window) in self.windows.iter().enumerate() {
+            window.window.set_title(&self.title(index));
+        }
+    }
+
+    fn window_index(&self, id: WindowId) -> Option<usize> {
+        self.windows
+            .iter()
+            .position(|window| window.window.id() == id)
+    }
+
+    fn request_redraw_all(&self) {
+        for window in &self.windows {
+            window.window.request_redraw();
+        }
+    }
+
+    fn draw(&mut self, index: usize) -> Result<(), RenderError> {
+        self.apply_pending_resize(index)?;
+        // minimized, there's nothing to render to, and in the background nobody's looking. Every
+        // skip here has to be known to `is_drawing` too, or the watchdog takes it for a hang
+        let window = &self.windows[index];
+        if is_zero_sized(window.window.inner_size()) || !window.focused || self.suspended {
+            return Ok(());
+        }
+        // resizes still reconfigure the surface on their own, so the first frame after resuming
+        // has the right size
+        if self.paused {
+            return Ok(());
+        }
+        // whatever the OS wants redrawn, only the step key gets to decide when a frame happens
+        if self.step && !window.step_pending {
+            return Ok(());
+        }
+
+        let clear_color = if self.windows[index].file_hovering {
+            FILE_HOVER_COLOR
+        } else {
+            self.clear_color(index)
+        };
+        let direction = self.camera_direction();
+        let Some(renderer) = &mut self.renderer else {
+            self.try_recreate_renderer();
+            return Ok(());
+        };
+
+        let frame_start = Instant::now();
+        let window = &mut self.windows[index];
+        renderer.set_touches(index, &window.touches, window.window.inner_size());
+        renderer.set_view(index, window.view);
+        if self.args.camera {
+            // after a while without moving, the camera shouldn't jump ahead by all of that time
+            let seconds = window.last_camera_update.elapsed().min(MAX_CAMERA_STEP);
+            window.last_camera_update = Instant::now();
+            let (dx, dy) = mem::take(&mut window.pending_look);
+            if (dx, dy) != (0.0, 0.0) {
+                debug!(index, dx, dy, "look");
+                window.camera.look(dx, dy);
+            }
+            window.camera.fly(direction, seconds.as_secs_f32());
+            renderer.set_view_projection(index, window.camera.view_projection());
+        }
+        let presented = renderer.render(index, clear_color, self.start.elapsed())?;
+        let gpu = renderer.gpu_frame_time();
+        let stress = renderer.stress_count(index);
+        if !presented {
+            window.window.request_redraw();
+            return Ok(());
+        }
+        // falling particles only move with new frames
+        if renderer.has_particles(index) && !self.step {
+            window.window.request_redraw();
+        }
+
+        if let Some(launch) = self.launch.take() {
+            let startup = renderer.startup_times();
+            info!(
+                "Time to first frame: {:?} (adapter after {:?}, device after {:?})",
+                launch.elapsed(),
+                startup.adapter - launch,
+                startup.device - launch,
+            );
+        }
+
+        // the frame limit would only drown out what the frame actually cost
+        let cpu = frame_start.elapsed();
+        self.frame_histogram.record(cpu);
+        self.frame_budget.record(cpu);
+        if let Some(benchmark) = &mut self.benchmark {
+            benchmark.record(frame_start, cpu);
+        }
+        self.limit_frame_rate();
+
+        let window = &mut self.windows[index];
+        window.step_pending = false;
+        // the title and HUD would only add their own cost to what's measured
+        if self.benchmark.is_some() {
+            return Ok(());
+        }
+        if let Some(report) = window.frame_times.frame_presented(cpu, gpu) {
+            if let Some(count) = stress {
+                let interval = report.interval.as_secs_f64();
+                info!(
+                    "{count} quads at {:.1} FPS — {}",
+                    1.0 / interval,
+                    report.cpu_and_gpu()
+                );
+            }
+            window.frame_report = Some(report);
+            self.update_title(index);
+
+            // shows up with the next frame anyway, no need to ask for one
+            if self.hud_visible {
+                self.set_hud_texts();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Gets the window into a state where the next frame might work out, or gives up on `err` if
+    /// there's nothing to be done about it.
+    fn recover(&mut self, index: usize, err: RenderError) -> Result<(), RenderError> {
+        match err {
+            RenderError::SurfaceLost => {
+                let size = self.windows[index].window.inner_size();
+                if let Some(renderer) = &mut self.renderer {
+                    renderer.resize(index, size)?;
+                }
+            }
+            RenderError::Timeout => debug!("Timed out waiting for the next frame"),
+            RenderError::DeviceLost => {
+                warn!("Device was lost, recreating the renderer");
+                // the old surfaces have to be gone before the windows can get new ones
+                self.renderer = None;
+                self.try_recreate_renderer();
+            }
+            RenderError::OutOfMemory | RenderError::DeviceError(_) => return Err(err),
+        }
+        self.windows[index].window.request_redraw();
+        Ok(())
+    }
+
+    /// Tries to set up everything GPU-related from scratch again, unless the last try was too
+    /// recent. The control flow makes sure to come back here in time for the next try.
+    fn try_recreate_renderer(&mut self) {
+        if self.last_recreation_attempt.elapsed() < RECREATION_INTERVAL {
+            return;
+        }
+        self.recreate_renderer();
+    }
+
+    /// Expects the old renderer to be gone already, so the windows can get new surfaces.
+    fn recreate_renderer(&mut self) {
+        self.last_recreation_attempt = Instant::now();
+
+        // SAFETY: same as in `new`, the windows outlive the renderer due to State's drop order
+        match unsafe { Renderer::new(&self.args, &window_refs(&self.windows)) } {
+            Ok(renderer) => {
+                info!("Recreated the renderer on {}", renderer.adapter_name());
+                // might have ended up on a different adapter this time
+                self.adapter_name = renderer.adapter_name();
+                self.features = renderer.features();
+                self.reset_titles();
+                self.desired_present_mode = Some(renderer.present_mode());
+                self.effective_present_mode = Some(renderer.present_mode());
+                self.renderer = Some(renderer);
+                // might have been recreated while nothing was focused
+                if let Err(err) = self.update_power_saving() {
+                    error!("Could not switch present mode: {err:#}");
+                }
+                self.update_hud();
+                self.request_redraw_all();
+            }
+            Err(err) => error!(
+                "Could not recreate the renderer, trying again in {RECREATION_INTERVAL:?}: {err:#}"
+            ),
+        }
+    }
+
+    /// Starts over with the opposite power preference, which usually means the other GPU on
+    /// laptops with two.
+    fn switch_power_preference(&mut self) {
+        let power = match self.args.power.unwrap_or_default() {
+            PowerPreference::LowPower => PowerPreference::HighPerformance,
+            PowerPreference::HighPerformance => PowerPreference::LowPower,
+        };
+        info!("Switching to a {power:?} adapter");
+        self.args.power = Some(power);
+
+        // everything GPU-related, pipelines included, belongs to the old device
+        self.renderer = None;
+        self.recreate_renderer();
+    }
+
+    fn suspend(&mut self) {
+        self.suspended = true;
+        if let Some(renderer) = &mut self.renderer {
+            renderer.suspend();
+        }
+    }
+
+    /// Also sent once on startup, where the surfaces are still around and nothing happens.
+    fn resume(&mut self) -> Result<()> {
+        self.suspended = false;
+        // a new renderer comes with new surfaces anyway
+        if let Some(renderer) = &mut self.renderer {
+            // SAFETY: same as in `new`, these are the same windows as back then
+            unsafe { renderer.resume(&window_refs(&self.windows)) }?;
+        }
+        self.request_redraw_all();
+        Ok(())
+    }
+
+    fn set_focused(&mut self, index: usize, focused: bool) -> Result<()> {
+        if !focused && self.windows[index].cursor_grabbed {
+            // otherwise the cursor might stay stuck even though the user went somewhere else
+            self.toggle_cursor_grab(index);
+        }
+        let window = &mut self.windows[index];
+        window.focused = focused;
+        // whatever happened in the meantime isn't on screen yet
+        if focused {
+            window.window.request_redraw();
+        }
+        self.update_power_saving()
+    }
+
+    /// Switches to Fifo while no window is focused if that was asked for, and back to the desired
+    /// present mode otherwise.
+    fn update_power_saving(&mut self) -> Result<()> {
+        let Some(renderer) = self
+            .renderer
+            .as_mut()
+            .filter(|_| self.args.vsync_off_when_unfocused)
+        else {
+            return Ok(());
+        };
+        let any_focused = self.windows.iter().any(|window| window.focused);
+        // every surface supports Fifo
+        let target = if any_focused {
+            self.desired_present_mode
+        } else {
+            Some(PresentMode::Fifo)
+        };
+        if target == self.effective_present_mode {
+            return Ok(());
+        }
+
+        if let Some(target) = target {
+            renderer.set_present_mode(target)?;
+        }
+        self.effective_present_mode = target;
+        self.update_hud();
+        Ok(())
+    }
+
+    /// Only spins the event loop when there's actually something to animate, in which case each
+    /// iteration ends with a redraw request. Called once the loop is about to wait.
+    fn update_control_flow(&self, flow: &mut ControlFlow) {
+        // otherwise nothing might happen until the OS sends something, so the time limits and the
+        // replay would never be noticed
+        let wake_up = [
+            self.deadline(),
+            self.watchdog_deadline(),
+            self.replayer.as_ref().and_then(Replayer::next_time),
+            self.renderer
+                .is_none()
+                .then(|| self.last_recreation_attempt + RECREATION_INTERVAL),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+
+        // without a renderer there's nothing to animate, only to retry
+        let any_focused = self.windows.iter().any(|window| window.focused);
+        if self.is_continuous() && any_focused && !self.suspended && self.renderer.is_some() {
+            flow.set_poll();
+            // this is what drives the animation, redraws the OS asks for are handled all the same
+            for window in self.windows.iter().filter(|window| window.focused) {
+                window.window.request_redraw();
+            }
+        } else if let Some(wake_up) = wake_up {
+            flow.set_wait_until(wake_up);
+        } else {
+            flow.set_wait();
+        }
+    }
+
+    /// Whether frames should be rendered one after another without waiting for anything.
+    fn is_continuous(&self) -> bool {
+        let flying = self.args.camera && self.camera_direction() != Vec3::ZERO;
+        let stressed = self.args.stress.is_some() || self.benchmark.is_some();
+        (self.animate || flying || stressed) && !self.step && !self.paused
+    }
+
+    /// Sleeps for whatever is left of the frame interval, the time spent rendering since the last
+    /// frame already counts towards it.
+    fn limit_frame_rate(&mut self) {
+        if let Some(interval) = self.frame_interval {
+            if let Some(remaining) = interval.checked_sub(self.last_frame.elapsed()) {
+                thread::sleep(remaining);
+            }
+        }
+        self.last_frame = Instant::now();
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.run_for.map(|run_for| self.start + run_for)
+    }
+
+    fn watchdog_deadline(&self) -> Option<Instant> {
+        if !self.is_drawing() {
+            return None;
+        }
+        let since = self.last_frame.max(self.watchdog_start);
+        self.watchdog.map(|watchdog| since + watchdog)
+    }
+
+    /// Whether frames should keep coming one after another, as opposed to idling or [`Self::draw`]
+    /// skipping them on purpose, which the watchdog shouldn't mistake for a hang. Has to be kept in
+    /// sync with the checks at the start of it.
+    fn is_drawing(&self) -> bool {
+        let any_visible = self
+            .windows
+            .iter()
+            .any(|window| window.focused && !is_zero_sized(window.window.inner_size()));
+        // idling until the OS asks for a frame is on purpose just as well
+        any_visible && !self.suspended && self.is_continuous()
+    }
+
+    /// Turns a hang into an error, so it doesn't go unnoticed in CI.
+    fn check_watchdog(&mut self) -> Result<()> {
+        // otherwise the time spent minimized or in the background would count once it's back
+        if !self.is_drawing() {
+            self.watchdog_start = Instant::now();
+        }
+        if let Some(deadline) = self.watchdog_deadline() {
+            if Instant::now() >= deadline {
+                bail!(
+                    "No frame was presented in the last {:?}, giving up",
+                    self.last_frame.elapsed()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Also once all frames of the benchmark are done.
+    fn is_time_up(&self) -> bool {
+        let benchmark_done = self.benchmark.as_ref().is_some_and(Benchmark::is_done);
+        benchmark_done
+            || self
+                .deadline()
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    fn clear_color(&self, index: usize) -> Color {
+        let cursor = self.windows[index].cursor.filter(|_| self.cursor_color);
+        if let Some(cursor) = cursor {
+            // normalized, so the corners are always the same colors regardless of scaling
+            let size = self.windows[index].window.inner_size();
+            let r = (cursor.x / f64::from(size.width.max(1))).clamp(0.0, 1.0);
+            let g = (cursor.y / f64::from(size.height.max(1))).clamp(0.0, 1.0);
+            let Color { b, a, .. } = self.background_color;
+            // premultiplied, in case the window is transparent
+            return Color {
+                r: r * a,
+                g: g * a,
+                b,
+                a,
+            };
+        }
+        if !self.animate {
+            return self.background_color;
+        }
+
+        // once around the hue wheel per period
+        let hue = (self.start.elapsed().as_secs_f64() / ANIMATION_PERIOD.as_secs_f64()).fract();
+        let Color { r, g, b, .. } = hsv_to_color(hue, self.args.saturation, self.args.value);
+        // premultiplied, in case the window is transparent
+        let alpha = self.background_color.a;
+        Color {
+            r: r * alpha,
+            g: g * alpha,
+            b: b * alpha,
+            a: alpha,
+        }
+    }
+
+    fn handle_window_event(
+        &mut self,
+        index: usize,
+        event: WindowEvent,
+        flow: &mut ControlFlow,
+    ) -> Result<()> {
+        if index == 0 {
+            self.record(&event);
+        }
+
+        // input goes through the same path as replayed input, so both behave exactly the same
+        if let Some(input) = RecordedEvent::from_window_event(&event) {
+            self.log_event(index, format!("{input:?}"));
+            return self.handle_input(index, input, flow);
+        }
+        match &event {
+            WindowEvent::Focused(focused) => self.log_event(index, format!("Focused({focused})")),
+            WindowEvent::Ime(ime) => self.log_event(index, format!("{ime:?}")),
+            WindowEvent::DroppedFile(path) => {
+                self.log_event(index, format!("DroppedFile({})", path.display()))
+            }
+            _ => (),
+        }
+
+        match event {
+            WindowEvent::Focused(focused) => self.set_focused(index, focused)?,
+            WindowEvent::Ime(ime) => self.ime(index, ime),
+            WindowEvent::HoveredFile(_) => self.set_file_hovering(index, true),
+            WindowEvent::HoveredFileCancelled => self.set_file_hovering(index, false),
+            WindowEvent::DroppedFile(path) => self.file_dropped(index, &path),
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                mut inner_size_writer,
+            } => self.scale_factor_changed(index, scale_factor, &mut inner_size_writer)?,
+            // the windows are all equal, so closing any of them ends the whole thing
+            WindowEvent::CloseRequested => flow.set_exit(),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn handle_input(
+        &mut self,
+        index: usize,
+        event: RecordedEvent,
+        flow: &mut ControlFlow,
+    ) -> Result<()> {
+        if let RecordedEvent::Keyboard { key, state, .. } = event {
+            match state {
+                ElementState::Pressed => self.held_keys.insert(key),
+                ElementState::Released => self.held_keys.remove(&key),
+            };
+        }
+
+        match event {
+            RecordedEvent::Touch {
+                id,
+                phase,
+                location,
+            } => {
+                debug!(index, id, ?phase, ?location, "touch");
+                self.touch(index, id, phase, location);
+            }
+            RecordedEvent::Resized { .. } => {
+                let window = &mut self.windows[index];
+                window.resize_pending = true;
+                window.window.request_redraw();
+            }
+            RecordedEvent::CursorMoved { position } => self.cursor_moved(index, position),
+            RecordedEvent::MouseInput {
+                button: MouseButton::Left,
+                state,
+            } => self.windows[index].dragging = state == ElementState::Pressed,
+            RecordedEvent::MouseWheel { delta } => self.zoom(index, delta),
+            RecordedEvent::Keyboard {
+                key: KeyCode::Escape,
+                state: ElementState::Pressed,
+                ..
+            } => flow.set_exit(),
+            RecordedEvent::Keyboard {
+                key,
+                state: ElementState::Pressed,
+                repeat: false,
+            } => self.key_pressed(index, key)?,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Feeds everything from the replay whose time has come through [`Self::handle_input`], as if
+    /// it happened in the first window.
+    fn replay_due_events(&mut self, flow: &mut ControlFlow) -> Result<()> {
+        let Some(replayer) = &mut self.replayer else {
+            return Ok(());
+        };
+        let mut due = Vec::new();
+        while let Some(event) = replayer.next_due() {
+            due.push(event);
+        }
+        if replayer.is_done() {
+            info!("Replay finished");
+            self.replayer = None;
+        }
+
+        for event in due {
+            // the window is the one thing the replay can't just pretend about
+            if let RecordedEvent::Resized { size } = event {
+                let _ = self.windows[0].window.request_inner_size(size);
+            }
+            self.handle_input(0, event, flow)?;
+        }
+        Ok(())
+    }
+
+    fn touch(&mut self, index: usize, id: u64, phase: TouchPhase, location: PhysicalPosition<f64>) {
+        let window = &mut self.windows[index];
+        match phase {
+            TouchPhase::Started => {
+                window.touches.insert(id, VecDeque::from([location]));
+            }
+            TouchPhase::Moved => {
+                let trail = window.touches.entry(id).or_default();
+                trail.push_back(location);
+                while trail.len() > self.trail_length + 1 {
+                    trail.pop_front();
+                }
+            }
+            // might be a touch we never saw starting, in which case there's nothing to remove
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                window.touches.remove(&id);
+            }
+        }
+        if let Some(renderer) = &mut self.renderer {
+            if self.args.paint {
+                renderer.paint_touch(index, id, phase, location);
+            }
+            if phase == TouchPhase::Started {
+                renderer.spawn_particles(index, id, location, self.start.elapsed());
+            }
+        }
+        window.window.request_redraw();
     }
 
-    fn draw(&mut self) -> Result<()> {
-        // very crude handling, the swapchain could be destroyed easily, but eh
-        let next_frame = self
-            .surface
-            .get_current_texture()
-            .context("Could not ask surface for the next texture")?;
+    /// Only logs what the IME sends, there's no text field to actually put it into.
+    fn ime(&self, index: usize, ime: Ime) {
+        match ime {
+            Ime::Enabled => {
+                info!(index, "IME enabled");
+                // the candidate window should show up somewhere near where the user is looking
+                let window = &self.windows[index];
+                let size = window.window.inner_size();
+                let position = window.cursor.unwrap_or(PhysicalPosition::new(
+                    f64::from(size.width) / 2.0,
+                    f64::from(size.height) / 2.0,
+                ));
+                window
+                    .window
+                    .set_ime_cursor_area(position, PhysicalSize::new(1, IME_CURSOR_HEIGHT));
+            }
+            Ime::Preedit(text, cursor) => info!(index, ?text, ?cursor, "IME preedit"),
+            Ime::Commit(text) => info!(index, ?text, "IME commit"),
+            Ime::Disabled => info!(index, "IME disabled"),
+        }
+    }
 
-        let preferred_format = self.surface.get_capabilities(&self.adapter).formats[0];
-        let next_frame_view = next_frame.texture.create_view(&TextureViewDescriptor {
-            format: Some(preferred_format),
-            ..TextureViewDescriptor::default()
+    fn set_file_hovering(&mut self, index: usize, hovering: bool) {
+        let window = &mut self.windows[index];
+        window.file_hovering = hovering;
+        window.window.request_redraw();
+    }
+
+    /// Images are shown in the window they were dropped on, anything else is only logged.
+    fn file_dropped(&mut self, index: usize, path: &Path) {
+        self.set_file_hovering(index, false);
+        if ImageFormat::from_path(path).is_err() {
+            info!(index, "Dropped {}, which isn't an image", path.display());
+            return;
+        }
+        let Some(renderer) = &mut self.renderer else {
+            error!(
+                "Could not show {}, the renderer is being recreated",
+                path.display()
+            );
+            return;
+        };
+        match renderer.load_image(index, path) {
+            Ok(()) => info!(index, "Showing dropped image {}", path.display()),
+            Err(err) => error!("{err:#}"),
+        }
+    }
+
+    /// Relative to where the camera looks, see [`Camera::fly`]. Zero if no movement key is held.
+    fn camera_direction(&self) -> Vec3 {
+        [
+            (KeyCode::KeyW, Vec3::Z),
+            (KeyCode::KeyS, Vec3::NEG_Z),
+            (KeyCode::KeyD, Vec3::X),
+            (KeyCode::KeyA, Vec3::NEG_X),
+        ]
+        .into_iter()
+        .filter(|(key, _)| self.held_keys.contains(key))
+        .map(|(_, direction)| direction)
+        .sum()
+    }
+
+    /// Collects the raw deltas for the camera of any window that has the cursor grabbed, they're
+    /// applied with its next frame. Unlike `CursorMoved`, these aren't accelerated or clamped to
+    /// the window.
+    fn mouse_motion(&mut self, (dx, dy): (f64, f64)) {
+        // grabbing works without the camera too, there's just nothing to look around with
+        if !self.args.camera {
+            return;
+        }
+        for window in self
+            .windows
+            .iter_mut()
+            .filter(|window| window.cursor_grabbed)
+        {
+            window.pending_look.0 += dx;
+            window.pending_look.1 += dy;
+            window.window.request_redraw();
+        }
+    }
+
+    /// Grabs and hides the cursor, or gives it back. Only warns if the platform can't grab.
+    fn toggle_cursor_grab(&mut self, index: usize) {
+        let window = &mut self.windows[index];
+        if window.cursor_grabbed {
+            if let Err(err) = window.window.set_cursor_grab(CursorGrabMode::None) {
+                warn!("Could not release the cursor: {err}");
+            }
+            window.window.set_cursor_visible(true);
+            window.cursor_grabbed = false;
+            window.pending_look = (0.0, 0.0);
+            return;
+        }
+
+        // not every platform can lock, confining works well enough for looking around
+        let grabbed = window
+            .window
+            .set_cursor_grab(CursorGrabMode::Locked)
+            .or_else(|_| window.window.set_cursor_grab(CursorGrabMode::Confined));
+        match grabbed {
+            Ok(()) => {
+                window.window.set_cursor_visible(false);
+                window.cursor_grabbed = true;
+            }
+            Err(err) => warn!("Could not grab the cursor: {err}"),
+        }
+    }
+
+    /// Drags the view along while the left button is held.
+    fn cursor_moved(&mut self, index: usize, position: PhysicalPosition<f64>) {
+        let window = &mut self.windows[index];
+        let previous = window.cursor.replace(position);
+        if self.cursor_color {
+            window.window.request_redraw();
+        }
+        self.update_title(index);
+        let window = &mut self.windows[index];
+        // while grabbed, the raw motion already looks around, panning as well would count the
+        // same movement twice
+        let Some(previous) = previous.filter(|_| window.dragging && !window.cursor_grabbed) else {
+            return;
+        };
+
+        // in normalized device coordinates, so the speed is the same at any resolution
+        let size = window.window.inner_size();
+        let (from, to) = (to_ndc(previous, size), to_ndc(position, size));
+        window.view.pan[0] += to[0] - from[0];
+        window.view.pan[1] += to[1] - from[1];
+        window.window.request_redraw();
+    }
+
+    /// Zooms around the cursor, so whatever is below it stays there.
+    fn zoom(&mut self, index: usize, delta: MouseScrollDelta) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            // trackpads report pixels instead, in much finer steps
+            MouseScrollDelta::PixelDelta(delta) => delta.y as f32 / PIXELS_PER_LINE,
+        };
+        let window = &mut self.windows[index];
+        // without knowing where the cursor is, the center is the best guess
+        let anchor = window.cursor.map_or([0.0, 0.0], |cursor| {
+            to_ndc(cursor, window.window.inner_size())
         });
+        window.view.zoom_around(anchor, ZOOM_PER_LINE.powf(lines));
+        window.window.request_redraw();
+    }
 
-        let mut encoder = self
-            .device
-            .create_command_encoder(&CommandEncoderDescriptor::default());
+    fn key_pressed(&mut self, index: usize, key: KeyCode) -> Result<()> {
+        match key {
+            KeyCode::KeyS if self.is_shortcut_held() => self.save_canvas(index),
+            KeyCode::KeyZ if self.is_shortcut_held() => self.undo_stroke(index),
+            // taken by the camera, the usual meanings would only get in the way
+            KeyCode::KeyW | KeyCode::KeyA | KeyCode::KeyS | KeyCode::KeyD if self.args.camera => (),
+            KeyCode::KeyG => self.toggle_cursor_grab(index),
+            KeyCode::F11 => self.toggle_fullscreen(index),
+            KeyCode::KeyS => self.screenshot(index),
+            KeyCode::KeyV if self.is_shortcut_held() => self.paste_clear_color(),
+            KeyCode::KeyV => {
+                if let Some(renderer) = &mut self.renderer {
+                    renderer.toggle_vsync()?;
+                    self.desired_present_mode = Some(renderer.present_mode());
+                    self.effective_present_mode = Some(renderer.present_mode());
+                }
+                self.update_hud();
+            }
+            KeyCode::KeyH => {
+                self.hud_visible = !self.hud_visible;
+                self.update_hud();
+            }
+            KeyCode::KeyL => self.print_event_log(),
+            KeyCode::KeyC => self.toggle_cursor_color(),
+            KeyCode::KeyP => self.switch_power_preference(),
+            KeyCode::KeyW => self.toggle_wireframe(),
+            KeyCode::ArrowRight | KeyCode::Space if self.step => {
+                let window = &mut self.windows[index];
+                window.step_pending = true;
+                window.window.request_redraw();
+            }
+            KeyCode::Space => self.toggle_paused(),
+            KeyCode::KeyT => self.switch_tone_mapping(),
+            KeyCode::BracketLeft => self.set_blur_radius(self.args.blur.saturating_sub(1)),
+            KeyCode::BracketRight => self.set_blur_radius(self.args.blur + 1),
+            KeyCode::Digit0 => self.set_background_color(default_background_color(&self.args)),
+            KeyCode::Digit1 => self.set_background_color(PRESET_COLORS[0]),
+            KeyCode::Digit2 => self.set_background_color(PRESET_COLORS[1]),
+            KeyCode::Digit3 => self.set_background_color(PRESET_COLORS[2]),
+            KeyCode::Digit4 => self.set_background_color(PRESET_COLORS[3]),
+            KeyCode::Digit5 => self.set_background_color(PRESET_COLORS[4]),
+            KeyCode::Digit6 => self.set_background_color(PRESET_COLORS[5]),
+            KeyCode::Digit7 => self.set_background_color(PRESET_COLORS[6]),
+            KeyCode::Digit8 => self.set_background_color(PRESET_COLORS[7]),
+            KeyCode::Digit9 => self.set_background_color(PRESET_COLORS[8]),
+            _ => (),
+        }
+        Ok(())
+    }
 
-        let background_color = wgpu::Color {
-            r: 0.05,
-            g: 0.05,
-            b: 0.05,
-            a: 1.0,
+    /// Has to be called whenever anything shown in the HUD might have changed.
+    fn update_hud(&mut self) {
+        self.set_hud_texts();
+        self.request_redraw_all();
+    }
+
+    fn set_hud_texts(&mut self) {
+        let Some(renderer) = &mut self.renderer else {
+            return;
+        };
+        for (index, window) in self.windows.iter().enumerate() {
+            let text = self.hud_visible.then(|| {
+                let mut text = format!(
+                    "Present mode: {:?}\nFormat: {:?}\nSamples: {}",
+                    renderer.present_mode(),
+                    renderer.surface_format(index),
+                    self.args.samples,
+                );
+                if let Some(operator) = self.args.tone_mapping {
+                    text.push_str(&format!("\nTone mapping: {operator:?}"));
+                }
+                text.push_str(&format!("\nGPU memory: ~{}", memory::allocated()));
+                if let Some(report) = &window.frame_report {
+                    text.push_str(&format!("\n{}", report.cpu_and_gpu()));
+                }
+                text
+            });
+            renderer.set_hud_text(index, text.as_deref());
+        }
+    }
+
+    fn toggle_paused(&mut self) {
+        self.paused = !self.paused;
+        if self.paused {
+            info!("Paused rendering");
+        } else {
+            info!("Resumed rendering");
+            // the pause shouldn't count as a hang
+            self.last_frame = Instant::now();
+            self.request_redraw_all();
+        }
+    }
+
+    fn toggle_cursor_color(&mut self) {
+        self.cursor_color = !self.cursor_color;
+        if self.cursor_color {
+            info!("Clear color follows the cursor now");
+        } else {
+            info!("Clear color doesn't follow the cursor anymore");
+        }
+        self.request_redraw_all();
+    }
+
+    /// Whether the usual modifier for shortcuts like copy and paste is held, Cmd on macOS and
+    /// Ctrl everywhere else.
+    fn is_shortcut_held(&self) -> bool {
+        if cfg!(target_os = "macos") {
+            self.modifiers.super_key()
+        } else {
+            self.modifiers.control_key()
+        }
+    }
+
+    /// Anything that isn't a color is just ignored, after a warning.
+    fn paste_clear_color(&mut self) {
+        let text = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text());
+        let color = match text {
+            Ok(text) => parse_clear_color(&text),
+            Err(err) => {
+                warn!("Could not read the clipboard: {err}");
+                return;
+            }
         };
-        let render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-            color_attachments: &[Some(RenderPassColorAttachment {
-                view: &next_frame_view,
-                resolve_target: None,
-                ops: Operations {
-                    load: LoadOp::Clear(background_color),
-                    store: true,
-                },
-            })],
-            ..RenderPassDescriptor::default()
+        match color {
+            Ok(color) => {
+                info!("Pasted clear color {color:?}");
+                self.set_background_color(color);
+            }
+            Err(err) => warn!("Clipboard doesn't contain a color: {err:#}"),
+        }
+    }
+
+    fn set_background_color(&mut self, color: Color) {
+        self.background_color = color;
+        self.request_redraw_all();
+    }
+
+    fn user_event(&mut self, event: UserEvent) {
+        match event {
+            UserEvent::SetClearColor(color) => self.set_background_color(color),
+            UserEvent::Screenshot => self.screenshot(0),
+            UserEvent::ShaderChanged => self.reload_shader(),
+        }
+    }
+
+    /// Keeps the last working shader if the new one can't be read or doesn't compile, it's
+    /// probably just half-written.
+    fn reload_shader(&mut self) {
+        let (Some(path), Some(renderer)) = (&self.args.shader, &mut self.renderer) else {
+            return;
+        };
+        let result = fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))
+            .and_then(|source| renderer.set_shader(&source));
+        match result {
+            Ok(()) => {
+                info!("Reloaded shader {}", path.display());
+                self.request_redraw_all();
+            }
+            Err(err) => error!("Could not reload shader: {err:#}"),
+        }
+    }
+
+    fn log_event(&mut self, window: usize, description: String) {
+        if self.event_log.len() == EVENT_LOG_LENGTH {
+            self.event_log.pop_front();
+        }
+        self.event_log.push_back(LoggedEvent {
+            time: Instant::now(),
+            window,
+            description,
         });
-        drop(render_pass);
+    }
+
+    fn print_event_log(&self) {
+        println!("Last {} events:", self.event_log.len());
+        let now = Instant::now();
+        for event in &self.event_log {
+            println!(
+                "  {:>8.3}s ago, window {}: {}",
+                (now - event.time).as_secs_f64(),
+                event.window,
+                event.description,
+            );
+        }
+    }
+
+    fn record(&mut self, event: &WindowEvent) {
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        // a broken recording isn't a reason to stop the app, but there's no point in continuing it
+        if let Err(err) = recorder.record(event) {
+            error!("Could not record event, stopping the recording: {err:#}");
+            self.recorder = None;
+        }
+    }
+
+    fn screenshot(&mut self, index: usize) {
+        let clear_color = self.clear_color(index);
+        let Some(renderer) = &mut self.renderer else {
+            error!("Could not take screenshot, the renderer is being recreated");
+            return;
+        };
+        // not worth taking the whole window down for
+        if let Err(err) = renderer.screenshot(index, "screenshot.png", clear_color) {
+            error!("Could not take screenshot: {err:#}");
+        }
+    }
+
+    fn toggle_wireframe(&mut self) {
+        if !self.features.contains(Features::POLYGON_MODE_LINE) {
+            warn!("Cannot show wireframes, the device does not support POLYGON_MODE_LINE");
+            return;
+        }
+        if let Some(renderer) = &mut self.renderer {
+            renderer.toggle_wireframe();
+        }
+        self.request_redraw_all();
+    }
+
+    /// Kept in the arguments, so a recreated renderer starts out with it as well.
+    fn set_blur_radius(&mut self, radius: u32) {
+        self.args.blur = radius.min(blur::MAX_RADIUS);
+        info!("Blur radius is now {}", self.args.blur);
+        if let Some(renderer) = &mut self.renderer {
+            renderer.set_blur_radius(self.args.blur);
+        }
+        self.request_redraw_all();
+    }
+
+    fn switch_tone_mapping(&mut self) {
+        let Some(operator) = &mut self.args.tone_mapping else {
+            warn!("Cannot switch the tone mapping operator, that needs --tone-mapping");
+            return;
+        };
+        // kept in the arguments, same as the blur radius
+        *operator = operator.next();
+        info!("Tone mapping with {operator:?} now");
+        if let Some(renderer) = &mut self.renderer {
+            renderer.set_tone_mapping(*operator);
+        }
+        self.update_hud();
+    }
+
+    fn undo_stroke(&mut self, index: usize) {
+        let Some(renderer) = &mut self.renderer else {
+            return;
+        };
+        if renderer.undo_stroke(index) {
+            self.windows[index].window.request_redraw();
+        }
+    }
 
-        self.queue.submit(iter::once(encoder.finish()));
-        next_frame.present();
+    fn save_canvas(&self, index: usize) {
+        let Some(renderer) = &self.renderer else {
+            error!("Could not save canvas, the renderer is being recreated");
+            return;
+        };
+        if let Err(err) = renderer.save_canvas(index, "canvas.png") {
+            error!("Could not save canvas: {err:#}");
+        }
+    }
+
+    /// The surface itself is reconfigured by the `Resized` event following this.
+    fn toggle_fullscreen(&self, index: usize) {
+        let window = &self.windows[index].window;
+        let fullscreen = match window.fullscreen() {
+            Some(_) => None,
+            None => Some(Fullscreen::Borderless(None)),
+        };
+        window.set_fullscreen(fullscreen);
+    }
 
+    /// Keeps the window at the same logical size. Everything sized after the surface is resized
+    /// right away, instead of staying at the old physical size until the next `Resized`, which
+    /// not every platform sends.
+    fn scale_factor_changed(
+        &mut self,
+        index: usize,
+        scale_factor: f64,
+        inner_size_writer: &mut InnerSizeWriter,
+    ) -> Result<()> {
+        let window = &mut self.windows[index];
+        let size = window
+            .window
+            .inner_size()
+            .to_logical::<f64>(window.scale_factor)
+            .to_physical(scale_factor);
+        window.scale_factor = scale_factor;
+        info!(
+            "Scale factor changed to {scale_factor}, resizing to {}x{}",
+            size.width, size.height,
+        );
+        // only fails if the window is already gone
+        let _ = inner_size_writer.request_inner_size(size);
+        self.resize_surface(index, size)
+            .context("Could not reconfigure surface")
+    }
+
+    fn apply_pending_resizes(&mut self) -> Result<(), RenderError> {
+        (0..self.windows.len()).try_for_each(|index| self.apply_pending_resize(index))
+    }
+
+    /// Configures the surface for whatever size the window has by now, if it was resized since
+    /// the last time.
+    fn apply_pending_resize(&mut self, index: usize) -> Result<(), RenderError> {
+        if !mem::take(&mut self.windows[index].resize_pending) {
+            return Ok(());
+        }
+        let size = self.windows[index].window.inner_size();
+        self.resize_surface(index, size)?;
+        self.update_title(index);
         Ok(())
     }
 
-    fn reconfigure_surface(&self) {
-        configure_surface(&self.surface, &self.device, &self.adapter, &self.window);
+    fn resize_surface(&mut self, index: usize, size: PhysicalSize<u32>) -> Result<(), RenderError> {
+        self.windows[index].camera.set_aspect(aspect(size));
+        // a new renderer picks up the current size anyway
+        let Some(renderer) = &mut self.renderer else {
+            return Ok(());
+        };
+        renderer.resize(index, size)
     }
 }
 
-fn configure_surface(surface: &Surface, device: &Device, adapter: &Adapter, window: &Window) {
-    let preferred_format = surface.get_capabilities(adapter).formats[0];
-    let window_size = window.inner_size();
-    surface.configure(
-        device,
-        &SurfaceConfiguration {
-            usage: TextureUsages::RENDER_ATTACHMENT,
-            format: preferred_format,
-            width: window_size.width,
-            height: window_size.height,
-            present_mode: PresentMode::Fifo,
-            alpha_mode: CompositeAlphaMode::Auto,
-            view_formats: Vec::new(),
-        },
-    );
+/// The gray used without --clear-color, half transparent if the window is.
+fn default_background_color(args: &Args) -> Color {
+    if args.transparent {
+        DEFAULT_TRANSPARENT_BACKGROUND_COLOR
+    } else {
+        DEFAULT_BACKGROUND_COLOR
+    }
+}
+
+/// The monitor at `index` in [`EventLoop::available_monitors`], or the primary one if there's none
+/// at that index, in which case the available ones are logged.
+fn choose_monitor<T>(event_loop: &EventLoop<T>, index: usize) -> Option<MonitorHandle> {
+    let monitors: Vec<_> = event_loop.available_monitors().collect();
+    if let Some(monitor) = monitors.get(index) {
+        return Some(monitor.clone());
+    }
+
+    let mut message =
+        format!("There is no monitor {index}, using the primary one instead. Available are:");
+    for (i, monitor) in monitors.iter().enumerate() {
+        let position = monitor.position();
+        let size = monitor.size();
+        message.push_str(&format!(
+            "\n- {i}: {} ({}x{} at {}, {}, scale factor {})",
+            monitor.name().as_deref().unwrap_or("unnamed"),
+            size.width,
+            size.height,
+            position.x,
+            position.y,
+            monitor.scale_factor(),
+        ));
+    }
+    warn!("{message}");
+    event_loop.primary_monitor()
+}
+
+/// From physical pixels with y pointing down to -1..=1 with y pointing up, like in the shaders.
+fn to_ndc(position: PhysicalPosition<f64>, size: PhysicalSize<u32>) -> [f32; 2] {
+    [
+        (position.x / f64::from(size.width.max(1)) * 2.0 - 1.0) as f32,
+        (1.0 - position.y / f64::from(size.height.max(1)) * 2.0) as f32,
+    ]
+}
+
+/// Width divided by height, without dividing by zero if minimized.
+fn aspect(size: PhysicalSize<u32>) -> f32 {
+    size.width as f32 / size.height.max(1) as f32
+}
+
+fn window_refs(windows: &[WindowState]) -> Vec<&Window> {
+    windows.iter().map(|window| &window.window).collect()
+}
+
+/// All of `hue`, `saturation` and `value` are in 0..=1, a hue of 0 and 1 both being red. The
+/// result is opaque.
+fn hsv_to_color(hue: f64, saturation: f64, value: f64) -> Color {
+    let sector = hue.rem_euclid(1.0) * 6.0;
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let min = value - chroma;
+    Color {
+        r: r + min,
+        g: g + min,
+        b: b + min,
+        a: 1.0,
+    }
+}
+
+/// Minimum time between two tries of recreating the renderer, so a GPU that stays gone doesn't
+/// make us spin.
+const RECREATION_INTERVAL: Duration = Duration::from_secs(1);
+
+/// From the top left corner of the monitor chosen with `--monitor`, in physical pixels, so the
+/// window decorations aren't cut off.
+const MONITOR_OFFSET: i32 = 64;
+
+/// How tall the area the IME is told the text cursor is in, in physical pixels.
+const IME_CURSOR_HEIGHT: u32 = 20;
+
+/// How many events [`State::event_log`] keeps around.
+const EVENT_LOG_LENGTH: usize = 32;
+
+/// Shown instead of the usual clear color while a file is dragged over a window.
+const FILE_HOVER_COLOR: Color = Color {
+    r: 0.1,
+    g: 0.3,
+    b: 0.6,
+    a: 1.0,
+};
+
+/// Picked with 1 to 9, 0 goes back to the default.
+const PRESET_COLORS: [Color; 9] = [
+    Color::BLACK,
+    Color::WHITE,
+    Color::RED,
+    Color::GREEN,
+    Color::BLUE,
+    Color {
+        r: 1.0,
+        g: 1.0,
+        b: 0.0,
+        a: 1.0,
+    },
+    Color {
+        r: 0.0,
+        g: 1.0,
+        b: 1.0,
+        a: 1.0,
+    },
+    Color {
+        r: 1.0,
+        g: 0.0,
+        b: 1.0,
+        a: 1.0,
+    },
+    Color {
+        r: 0.5,
+        g: 0.5,
+        b: 0.5,
+        a: 1.0,
+    },
+];
+
+/// The most time a single frame moves the camera for.
+const MAX_CAMERA_STEP: Duration = Duration::from_millis(100);
+
+/// How much one line of scrolling zooms in, scrolling the other way zooms out by the same.
+const ZOOM_PER_LINE: f32 = 1.1;
+/// How many pixels of a trackpad scroll count as one line of a mouse wheel.
+const PIXELS_PER_LINE: f32 = 40.0;
+
+/// How long the animated clear color takes for one full trip around the hue wheel.
+const ANIMATION_PERIOD: Duration = Duration::from_secs(6);
+
+const FRAME_TIME_SAMPLES: usize = 64;
+const FRAME_TIME_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Upper bounds of the printed histogram buckets, the last bucket has none.
+const HISTOGRAM_BUCKETS: [Duration; 3] = [
+    Duration::from_millis(8),
+    Duration::from_millis(16),
+    Duration::from_millis(33),
+];
+/// The percentiles are only as precise as this.
+const HISTOGRAM_RESOLUTION: Duration = Duration::from_micros(100);
+/// Frames slower than this are only counted, so the histogram stays the same size forever.
+const HISTOGRAM_RANGE: Duration = Duration::from_millis(100);
+/// How many characters the longest bar is wide.
+const HISTOGRAM_BAR_WIDTH: usize = 40;
+/// Overruns of the frame budget within this long after a logged one are only summarized.
+const OVERRUN_SUMMARY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Rolling average over the durations between the last few presented frames, and what they cost
+/// on the CPU.
+struct FrameTimes {
+    samples: [Duration; FRAME_TIME_SAMPLES],
+    cpu_samples: [Duration; FRAME_TIME_SAMPLES],
+    next: usize,
+    filled: usize,
+    last_frame: Instant,
+    last_report: Instant,
+}
+
+impl FrameTimes {
+    fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            samples: [Duration::ZERO; FRAME_TIME_SAMPLES],
+            cpu_samples: [Duration::ZERO; FRAME_TIME_SAMPLES],
+            next: 0,
+            filled: 0,
+            last_frame: now,
+            last_report: now,
+        }
+    }
+
+    /// Records that a frame was just presented, which took `cpu` to record and submit. `gpu` is
+    /// just passed through, if the GPU time could be measured at all. Returns the averages if
+    /// it's time to report them again.
+    fn frame_presented(&mut self, cpu: Duration, gpu: Option<Duration>) -> Option<FrameReport> {
+        let now = Instant::now();
+        self.samples[self.next] = now - self.last_frame;
+        self.cpu_samples[self.next] = cpu;
+        self.next = (self.next + 1) % FRAME_TIME_SAMPLES;
+        self.filled = (self.filled + 1).min(FRAME_TIME_SAMPLES);
+        self.last_frame = now;
+
+        if now - self.last_report < FRAME_TIME_REPORT_INTERVAL {
+            return None;
+        }
+        self.last_report = now;
+
+        let average = |samples: &[Duration]| {
+            samples[..self.filled].iter().sum::<Duration>() / self.filled as u32
+        };
+        Some(FrameReport {
+            interval: average(&self.samples),
+            cpu: average(&self.cpu_samples),
+            gpu,
+        })
+    }
+}
+
+struct FrameReport {
+    /// Between two presented frames.
+    interval: Duration,
+    cpu: Duration,
+    /// `None` if the adapter can't measure it.
+    gpu: Option<Duration>,
+}
+
+impl FrameReport {
+    /// Like `CPU 1.20ms, GPU 0.80ms`, whichever is larger is likely the bottleneck.
+    fn cpu_and_gpu(&self) -> String {
+        let gpu = match self.gpu {
+            Some(gpu) => format!("{:.2}ms", gpu.as_secs_f64() * 1000.0),
+            None => "n/a".to_string(),
+        };
+        format!("CPU {:.2}ms, GPU {gpu}", self.cpu.as_secs_f64() * 1000.0)
+    }
+}
+
+/// Warns about frames over the budget, but a hitch usually comes with many of them, so only the
+/// first one is logged right away and the ones after it together in a summary.
+struct FrameBudget {
+    budget: Duration,
+    /// When the last overrun was logged on its own, `None` if there's no summary pending.
+    logged_at: Option<Instant>,
+    /// Overruns since then.
+    coalesced: u32,
+}
+
+impl FrameBudget {
+    fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            logged_at: None,
+            coalesced: 0,
+        }
+    }
+
+    fn record(&mut self, cpu: Duration) {
+        if let Some(logged_at) = self.logged_at {
+            let since = logged_at.elapsed();
+            if since >= OVERRUN_SUMMARY_INTERVAL {
+                if self.coalesced > 0 {
+                    warn!(
+                        "{} frames over budget in the last {:.1}s",
+                        self.coalesced,
+                        since.as_secs_f64(),
+                    );
+                }
+                self.logged_at = None;
+                self.coalesced = 0;
+            }
+        }
+
+        if cpu <= self.budget {
+            return;
+        }
+        if self.logged_at.is_some() {
+            self.coalesced += 1;
+            return;
+        }
+        warn!(
+            "Frame took {:.2}ms on the CPU, over the budget of {:.2}ms",
+            cpu.as_secs_f64() * 1000.0,
+            self.budget.as_secs_f64() * 1000.0,
+        );
+        self.logged_at = Some(Instant::now());
+    }
+}
+
+/// Keeps the CPU time of every frame for --frames, there's a fixed number of them anyway.
+struct Benchmark {
+    frames: usize,
+    samples: Vec<Duration>,
+    /// When the first frame started.
+    start: Option<Instant>,
+    /// When the last frame was done.
+    end: Option<Instant>,
+}
+
+impl Benchmark {
+    fn new(frames: usize) -> Self {
+        Self {
+            frames,
+            samples: Vec::with_capacity(frames),
+            start: None,
+            end: None,
+        }
+    }
+
+    fn record(&mut self, frame_start: Instant, cpu: Duration) {
+        if self.is_done() {
+            return;
+        }
+        self.start.get_or_insert(frame_start);
+        self.samples.push(cpu);
+        if self.is_done() {
+            self.end = Some(Instant::now());
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.samples.len() >= self.frames
+    }
+
+    fn print(&self) {
+        let (Some(start), Some(end)) = (self.start, self.end) else {
+            println!(
+                "Benchmark stopped after {} of {} frames",
+                self.samples.len(),
+                self.frames
+            );
+            return;
+        };
+
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        // nearest rank, so it's always one of the actual samples
+        let percentile = |fraction: f64| {
+            let rank = (sorted.len() as f64 * fraction).ceil() as usize;
+            sorted[rank.clamp(1, sorted.len()) - 1]
+        };
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let mean = sorted.iter().sum::<Duration>() / sorted.len() as u32;
+        println!(
+            "Benchmark of {} frames in {:.1}ms: mean {:.2}ms, median {:.2}ms, p95 {:.2}ms, \
+            p99 {:.2}ms",
+            self.frames,
+            ms(end - start),
+            ms(mean),
+            ms(percentile(0.5)),
+            ms(percentile(0.95)),
+            ms(percentile(0.99)),
+        );
+    }
+}
+
+/// Counts frames by how long they took, in bins of [`HISTOGRAM_RESOLUTION`], so nothing grows
+/// with the number of frames.
+struct FrameHistogram {
+    bins: Vec<u64>,
+    /// Frames that took longer than [`HISTOGRAM_RANGE`].
+    overflow: u64,
+    count: u64,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+impl FrameHistogram {
+    fn new() -> Self {
+        let bins = (HISTOGRAM_RANGE.as_nanos() / HISTOGRAM_RESOLUTION.as_nanos()) as usize;
+        Self {
+            bins: vec![0; bins],
+            overflow: 0,
+            count: 0,
+            total: Duration::ZERO,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+        }
+    }
+
+    fn record(&mut self, duration: Duration) {
+        let bin = (duration.as_nanos() / HISTOGRAM_RESOLUTION.as_nanos()) as usize;
+        match self.bins.get_mut(bin) {
+            Some(count) => *count += 1,
+            None => self.overflow += 1,
+        }
+        self.count += 1;
+        self.total += duration;
+        self.min = self.min.min(duration);
+        self.max = self.max.max(duration);
+    }
+
+    /// Upper bound of the bin the given fraction of frames is in or below of. `None` if that's
+    /// beyond [`HISTOGRAM_RANGE`].
+    fn percentile(&self, fraction: f64) -> Option<Duration> {
+        let target = (self.count as f64 * fraction).ceil() as u64;
+        let mut seen = 0;
+        for (i, count) in self.bins.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return Some(HISTOGRAM_RESOLUTION * (i as u32 + 1));
+            }
+        }
+        None
+    }
+
+    fn print(&self) {
+        if self.count == 0 {
+            return;
+        }
+
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let p99 = match self.percentile(0.99) {
+            Some(p99) => format!("{:.1}ms", ms(p99)),
+            None => format!(">{:.0}ms", ms(HISTOGRAM_RANGE)),
+        };
+        println!(
+            "Frame times over {} frames: min {:.1}ms, mean {:.1}ms, p99 {p99}, max {:.1}ms",
+            self.count,
+            ms(self.min),
+            ms(self.total / self.count as u32),
+            ms(self.max),
+        );
+
+        // every fine bin falls into exactly one of the coarse buckets
+        let mut buckets = [0; HISTOGRAM_BUCKETS.len() + 1];
+        for (i, count) in self.bins.iter().enumerate() {
+            let start = HISTOGRAM_RESOLUTION * i as u32;
+            let bucket = HISTOGRAM_BUCKETS.partition_point(|&bound| bound <= start);
+            buckets[bucket] += count;
+        }
+        buckets[HISTOGRAM_BUCKETS.len()] += self.overflow;
+
+        let largest = buckets.iter().copied().max().unwrap_or(0).max(1);
+        let bounds = HISTOGRAM_BUCKETS.map(|bound| bound.as_millis());
+        for (i, count) in buckets.into_iter().enumerate() {
+            let label = if i == 0 {
+                format!("<{}ms", bounds[0])
+            } else if i == bounds.len() {
+                format!(">{}ms", bounds[i - 1])
+            } else {
+                format!("{}-{}ms", bounds[i - 1], bounds[i])
+            };
+            let bar = "#".repeat((count as usize * HISTOGRAM_BAR_WIDTH).div_ceil(largest as usize));
+            println!("{label:>8} {count:>8} {bar}");
+        }
+    }
 }
@@ -0,0 +1,118 @@
+//! A rough tally of the GPU memory taken by textures, worked out from their dimensions and
+//! formats. Drivers add padding and compression on top of that, so this won't match what they
+//! report, but it's plenty to spot a texture way larger than intended.
+
+use std::{
+    fmt,
+    ops::Deref,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use tracing::debug;
+use wgpu::{Device, Texture, TextureDescriptor, TextureFormat};
+use winit::dpi::PhysicalSize;
+
+/// How many textures the swapchain of a surface is assumed to have, wgpu doesn't say.
+const SURFACE_TEXTURES: u64 = 3;
+
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+
+/// Everything counted right now, across all devices.
+pub fn allocated() -> Bytes {
+    Bytes(ALLOCATED.load(Ordering::Relaxed))
+}
+
+/// Counts towards [`allocated`] for as long as it's around.
+#[derive(Debug)]
+pub struct Allocation {
+    bytes: u64,
+}
+
+impl Allocation {
+    pub fn new(label: &str, bytes: u64) -> Self {
+        let total = ALLOCATED.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        debug!(
+            "Allocated {} for {label}, {} in total",
+            Bytes(bytes),
+            Bytes(total)
+        );
+        Self { bytes }
+    }
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        ALLOCATED.fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
+
+/// Counts towards [`allocated`] until it's dropped. Keep it around for as long as any views of
+/// it, those keep the memory alive as well.
+#[derive(Debug)]
+pub struct TrackedTexture {
+    texture: Texture,
+    _allocation: Allocation,
+}
+
+impl Deref for TrackedTexture {
+    type Target = Texture;
+
+    fn deref(&self) -> &Texture {
+        &self.texture
+    }
+}
+
+pub fn create_texture(device: &Device, descriptor: &TextureDescriptor) -> TrackedTexture {
+    let size = descriptor.size;
+    let label = format!(
+        "{} ({}x{} {:?}, {} samples)",
+        descriptor.label.unwrap_or("unnamed texture"),
+        size.width,
+        size.height,
+        descriptor.format,
+        descriptor.sample_count,
+    );
+    let allocation = Allocation::new(&label, texture_bytes(descriptor));
+    TrackedTexture {
+        texture: device.create_texture(descriptor),
+        _allocation: allocation,
+    }
+}
+
+/// For the textures of a surface configured with `format` at `size`, which wgpu creates on its
+/// own.
+pub fn surface(format: TextureFormat, size: PhysicalSize<u32>) -> Allocation {
+    let texel = u64::from(format.block_size(None).unwrap_or(4));
+    let texels = u64::from(size.width) * u64::from(size.height);
+    let label = format!("surface ({}x{} {format:?})", size.width, size.height);
+    Allocation::new(&label, texels * texel * SURFACE_TEXTURES)
+}
+
+/// All mip levels and samples together.
+fn texture_bytes(descriptor: &TextureDescriptor) -> u64 {
+    let format = descriptor.format;
+    let (block_width, block_height) = format.block_dimensions();
+    // depth formats without a fixed layout are most likely 4 bytes anyway
+    let block_size = u64::from(format.block_size(None).unwrap_or(4));
+    let levels: u64 = (0..descriptor.mip_level_count)
+        .filter_map(|level| descriptor.mip_level_size(level))
+        .map(|size| {
+            let size = size.physical_size(format);
+            let blocks = u64::from(size.width / block_width)
+                * u64::from(size.height / block_height)
+                * u64::from(size.depth_or_array_layers);
+            blocks * block_size
+        })
+        .sum();
+    levels * u64::from(descriptor.sample_count)
+}
+
+/// Shown in MiB, since that's the scale anything interesting happens at.
+#[derive(Debug, Clone, Copy)]
+pub struct Bytes(pub u64);
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1} MiB", self.0 as f64 / (1024.0 * 1024.0))
+    }
+}
@@ -0,0 +1,290 @@
+//! Bursts of particles wherever a finger touches down, falling under gravity. The simulation runs
+//! entirely in a compute shader, the CPU only writes the freshly spawned ones.
+
+use std::{f32::consts::TAU, mem, time::Duration};
+
+use bytemuck::{Pod, Zeroable};
+use tracing::warn;
+use wgpu::{
+    vertex_attr_array, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, BlendState, Buffer, BufferAddress, BufferBindingType,
+    BufferDescriptor, BufferUsages, ColorTargetState, ColorWrites, CommandEncoder,
+    ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, Device, FragmentState,
+    MultisampleState, PipelineLayoutDescriptor, PrimitiveState, Queue, RenderPass, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat,
+    VertexBufferLayout, VertexState, VertexStepMode,
+};
+use winit::dpi::PhysicalPosition;
+
+use crate::{globals::Globals, touch};
+
+/// Has to match `@workgroup_size` in the shader.
+const WORKGROUP_SIZE: u32 = 64;
+/// Upper bound, the device limits might lower it further.
+const MAX_PARTICLES: u32 = 4096;
+/// Spawned per touch.
+const BURST: u32 = 64;
+/// Longest a particle lives, in seconds.
+const LIFETIME: f32 = 2.0;
+/// In physical pixels per second.
+const MIN_SPEED: f32 = 100.0;
+const MAX_SPEED: f32 = 400.0;
+/// So a particle doesn't fly through half the screen after a hitch, or after being paused.
+const MAX_STEP: Duration = Duration::from_millis(100);
+
+/// Laid out the same as in the shader, where it's both a storage buffer element and an instance.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct Particle {
+    /// In physical pixels from the top left corner.
+    position: [f32; 2],
+    /// In physical pixels per second.
+    velocity: [f32; 2],
+    color: [f32; 4],
+    /// Seconds left until it's gone, not drawn or simulated anymore at 0 or below.
+    life: f32,
+    _padding: [f32; 3],
+}
+
+pub struct Particles {
+    compute_pipeline: ComputePipeline,
+    render_pipeline: RenderPipeline,
+    particles: Buffer,
+    step_buffer: Buffer,
+    bind_group: BindGroup,
+    capacity: u32,
+
+    /// Where the next burst starts, the oldest particles are overwritten first.
+    cursor: u32,
+    /// For spreading the particles of a burst, doesn't need to be any good.
+    rng: u32,
+    /// Of the last [`Self::update`], to know how far to simulate.
+    last_elapsed: Duration,
+    /// After this, all particles are gone.
+    alive_until: Duration,
+}
+
+impl Particles {
+    /// Returns `None` and logs why if the device has no compute shaders, which is fine since
+    /// everything else works without them.
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals: &Globals,
+    ) -> Option<Self> {
+        let limits = device.limits();
+        if limits.max_storage_buffers_per_shader_stage == 0
+            || limits.max_compute_invocations_per_workgroup < WORKGROUP_SIZE
+            || limits.max_compute_workgroup_size_x < WORKGROUP_SIZE
+        {
+            warn!("Device has no compute shaders, leaving out particles");
+            return None;
+        }
+        let capacity = MAX_PARTICLES
+            .min(limits.max_storage_buffer_binding_size / mem::size_of::<Particle>() as u32)
+            .min(limits.max_compute_workgroups_per_dimension * WORKGROUP_SIZE);
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("particles shader"),
+            source: ShaderSource::Wgsl(include_str!("particles.wgsl").into()),
+        });
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("particles layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        // zeroed, so every particle starts out dead
+        let particles = device.create_buffer(&BufferDescriptor {
+            label: Some("particles"),
+            size: (capacity as usize * mem::size_of::<Particle>()) as BufferAddress,
+            // read as instances for drawing, since storage buffers in vertex shaders are rarer
+            usage: BufferUsages::STORAGE | BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let step_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("particles step"),
+            // uniform buffers are padded to 16 bytes anyway
+            size: mem::size_of::<[f32; 4]>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("particles bind group"),
+            layout: &layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: step_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: particles.as_entire_binding(),
+                },
+            ],
+        });
+
+        let compute_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("particles compute pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let compute_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("particles compute pipeline"),
+            layout: Some(&compute_layout),
+            module: &shader,
+            entry_point: "cs_main",
+        });
+
+        let render_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("particles render pipeline layout"),
+            bind_group_layouts: &[&globals.layout],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("particles render pipeline"),
+            layout: Some(&render_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[VertexBufferLayout {
+                    array_stride: mem::size_of::<Particle>() as BufferAddress,
+                    step_mode: VertexStepMode::Instance,
+                    attributes: &vertex_attr_array![
+                        0 => Float32x2, 1 => Float32x2, 2 => Float32x4, 3 => Float32,
+                    ],
+                }],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: sample_count,
+                ..MultisampleState::default()
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+
+        Some(Self {
+            compute_pipeline,
+            render_pipeline,
+            particles,
+            step_buffer,
+            bind_group,
+            capacity,
+            cursor: 0,
+            rng: 0x9E37_79B9,
+            last_elapsed: Duration::ZERO,
+            alive_until: Duration::ZERO,
+        })
+    }
+
+    /// Spawns a burst at `location`, in the color of the finger with the given touch ID.
+    /// `elapsed` is the time since the start, the same as passed to [`Self::update`].
+    pub fn spawn(
+        &mut self,
+        queue: &Queue,
+        id: u64,
+        location: PhysicalPosition<f64>,
+        elapsed: Duration,
+    ) {
+        let color = touch::color(id);
+        let burst: Vec<_> = (0..BURST.min(self.capacity))
+            .map(|i| {
+                let angle = (i as f32 + self.random()) / BURST as f32 * TAU;
+                let speed = MIN_SPEED + (MAX_SPEED - MIN_SPEED) * self.random();
+                Particle {
+                    position: [location.x as f32, location.y as f32],
+                    // upwards a bit, so they fountain out before falling
+                    velocity: [angle.cos() * speed, angle.sin() * speed - MIN_SPEED],
+                    color,
+                    life: LIFETIME * (0.5 + 0.5 * self.random()),
+                    _padding: [0.0; 3],
+                }
+            })
+            .collect();
+
+        // wraps around at the end of the buffer
+        let (head, tail) = burst.split_at(burst.len().min((self.capacity - self.cursor) as usize));
+        let offset = (self.cursor as usize * mem::size_of::<Particle>()) as BufferAddress;
+        queue.write_buffer(&self.particles, offset, bytemuck::cast_slice(head));
+        if !tail.is_empty() {
+            queue.write_buffer(&self.particles, 0, bytemuck::cast_slice(tail));
+        }
+        self.cursor = (self.cursor + burst.len() as u32) % self.capacity;
+        // not the last update, that might be long ago if nothing was rendered in the meantime
+        self.alive_until = elapsed + Duration::from_secs_f32(LIFETIME);
+    }
+
+    /// Whether there are still particles around, which need new frames to keep moving.
+    pub fn is_alive(&self) -> bool {
+        self.last_elapsed < self.alive_until
+    }
+
+    /// `elapsed` is the time since the start, the particles move by however much of it passed
+    /// since the last call with the next [`Self::simulate`].
+    pub fn update(&mut self, queue: &Queue, elapsed: Duration) {
+        let step = elapsed.saturating_sub(self.last_elapsed).min(MAX_STEP);
+        self.last_elapsed = elapsed;
+        queue.write_buffer(
+            &self.step_buffer,
+            0,
+            bytemuck::cast_slice(&[step.as_secs_f32(), 0.0, 0.0, 0.0]),
+        );
+    }
+
+    /// Has to be recorded before the pass [`Self::draw`] is called in.
+    pub fn simulate(&self, encoder: &mut CommandEncoder) {
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("particles pass"),
+        });
+        compute_pass.set_pipeline(&self.compute_pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.dispatch_workgroups(self.capacity.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+
+    /// Expects the globals to be bound at group 0 already.
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_vertex_buffer(0, self.particles.slice(..));
+        render_pass.draw(0..6, 0..self.capacity);
+    }
+
+    /// Somewhere in `0.0..1.0`.
+    fn random(&mut self) -> f32 {
+        // xorshift32
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        (self.rng >> 8) as f32 / (1 << 24) as f32
+    }
+}
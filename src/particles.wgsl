@@ -0,0 +1,99 @@
+struct Globals {
+    time: f32,
+    grid_size: f32,
+    resolution: vec2<f32>,
+    gradient_start: vec4<f32>,
+    gradient_end: vec4<f32>,
+    gradient_horizontal: u32,
+    zoom: f32,
+    pan: vec2<f32>,
+    view_projection: mat4x4<f32>,
+    pixel_projection: mat4x4<f32>,
+}
+
+struct Particle {
+    // in physical pixels from the top left corner
+    position: vec2<f32>,
+    // in physical pixels per second
+    velocity: vec2<f32>,
+    color: vec4<f32>,
+    // in seconds, dead at 0 or below
+    life: f32,
+}
+
+struct Step {
+    // in seconds since the last simulation
+    seconds: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> params: Step;
+@group(0) @binding(1)
+var<storage, read_write> particles: array<Particle>;
+
+// in physical pixels per second squared, down is positive like in the window
+const GRAVITY: f32 = 600.0;
+// side length of the square each particle is drawn as, in physical pixels
+const SIZE: f32 = 4.0;
+// fades out for the last this many seconds
+const FADE: f32 = 0.5;
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= arrayLength(&particles) {
+        return;
+    }
+    var particle = particles[id.x];
+    if particle.life <= 0.0 {
+        return;
+    }
+
+    particle.velocity.y += GRAVITY * params.seconds;
+    particle.position += particle.velocity * params.seconds;
+    particle.life -= params.seconds;
+    particles[id.x] = particle;
+}
+
+// in its own group 0, since the render pipeline doesn't see the compute bindings
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+struct Instance {
+    @location(0) position: vec2<f32>,
+    @location(1) velocity: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) life: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, particle: Instance) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-0.5, -0.5),
+        vec2<f32>(0.5, -0.5),
+        vec2<f32>(0.5, 0.5),
+        vec2<f32>(-0.5, -0.5),
+        vec2<f32>(0.5, 0.5),
+        vec2<f32>(-0.5, 0.5),
+    );
+
+    var out: VertexOutput;
+    if particle.life <= 0.0 {
+        // behind the near plane, so it's clipped away entirely
+        out.position = vec4<f32>(0.0, 0.0, -1.0, 1.0);
+        return out;
+    }
+    let pixel = particle.position + corners[index] * SIZE;
+    out.position = globals.pixel_projection * vec4<f32>(pixel, 0.0, 1.0);
+    out.color = vec4<f32>(particle.color.rgb, particle.color.a * clamp(particle.life / FADE, 0.0, 1.0));
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,95 @@
+//! Copying textures back to the CPU, for screenshots and the like.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use wgpu::{
+    BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Device, Extent3d, ImageCopyBuffer,
+    ImageCopyTexture, ImageDataLayout, Maintain, MapMode, Origin3d, Queue, Texture, TextureAspect,
+    TextureFormat, COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+/// Reads back a 2D texture with `COPY_SRC` usage as tightly packed RGBA8 rows. Only 8-bit RGBA
+/// and BGRA formats are supported, the latter gets swizzled.
+pub fn read_texture_rgba(device: &Device, queue: &Queue, texture: &Texture) -> Result<Vec<u8>> {
+    let format = texture.format();
+    let swap_red_blue = match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => false,
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => true,
+        _ => bail!("Reading back {format:?} textures is not supported"),
+    };
+
+    let (width, height) = (texture.width(), texture.height());
+    let unpadded_bytes_per_row = width * 4;
+    // each row in the buffer has to start at a multiple of 256 bytes
+    let padded_bytes_per_row =
+        unpadded_bytes_per_row.next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("readback buffer"),
+        size: u64::from(padded_bytes_per_row) * u64::from(height),
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+    encoder.copy_texture_to_buffer(
+        ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(Maintain::Wait);
+    receiver
+        .recv()
+        .context("Readback buffer was dropped before being mapped")?
+        .context("Could not map readback buffer")?;
+
+    let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+    for row in slice
+        .get_mapped_range()
+        .chunks_exact(padded_bytes_per_row as usize)
+    {
+        pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+    }
+    buffer.unmap();
+
+    if swap_red_blue {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    Ok(pixels)
+}
+
+pub fn save_png(path: impl AsRef<Path>, width: u32, height: u32, pixels: Vec<u8>) -> Result<()> {
+    let path = path.as_ref();
+    let image = image::RgbaImage::from_raw(width, height, pixels)
+        .context("Pixel data does not match the image dimensions")?;
+    image
+        .save(path)
+        .with_context(|| format!("Could not write {}", path.display()))
+}
@@ -0,0 +1,164 @@
+//! Writing the interesting window events to a JSONL file, one event per line, and reading them
+//! back again, so input bugs can be reproduced later on.
+
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{LineWriter, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent},
+    keyboard::KeyCode,
+};
+
+/// The parts of a [`WindowEvent`] worth keeping. Everything platform-specific like device IDs is
+/// left out, since it wouldn't mean anything on replay anyway.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecordedEvent {
+    Touch {
+        id: u64,
+        phase: TouchPhase,
+        location: PhysicalPosition<f64>,
+    },
+    Keyboard {
+        key: KeyCode,
+        state: ElementState,
+        repeat: bool,
+    },
+    CursorMoved {
+        position: PhysicalPosition<f64>,
+    },
+    MouseInput {
+        button: MouseButton,
+        state: ElementState,
+    },
+    MouseWheel {
+        delta: MouseScrollDelta,
+    },
+    Resized {
+        size: PhysicalSize<u32>,
+    },
+}
+
+impl RecordedEvent {
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        Some(match *event {
+            WindowEvent::Touch(touch) => Self::Touch {
+                id: touch.id,
+                phase: touch.phase,
+                location: touch.location,
+            },
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key,
+                        state,
+                        repeat,
+                        ..
+                    },
+                ..
+            } => Self::Keyboard {
+                key: physical_key,
+                state,
+                repeat,
+            },
+            WindowEvent::CursorMoved { position, .. } => Self::CursorMoved { position },
+            WindowEvent::MouseInput { button, state, .. } => Self::MouseInput { button, state },
+            WindowEvent::MouseWheel { delta, .. } => Self::MouseWheel { delta },
+            WindowEvent::Resized(size) => Self::Resized { size },
+            _ => return None,
+        })
+    }
+}
+
+/// One line of the log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// Seconds since the recording started.
+    pub time: f64,
+    #[serde(flatten)]
+    pub event: RecordedEvent,
+}
+
+pub struct Recorder {
+    // flushes after every line, so nothing is lost if the app goes down mid-recording
+    file: LineWriter<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Could not create event log {}", path.display()))?;
+        Ok(Self {
+            file: LineWriter::new(file),
+            start: Instant::now(),
+        })
+    }
+
+    /// Does nothing for events that aren't interesting enough to be recorded.
+    pub fn record(&mut self, event: &WindowEvent) -> Result<()> {
+        let Some(event) = RecordedEvent::from_window_event(event) else {
+            return Ok(());
+        };
+        let entry = Entry {
+            time: self.start.elapsed().as_secs_f64(),
+            event,
+        };
+
+        serde_json::to_writer(&mut self.file, &entry)?;
+        writeln!(self.file)?;
+        Ok(())
+    }
+}
+
+pub struct Replayer {
+    entries: VecDeque<Entry>,
+    start: Instant,
+}
+
+impl Replayer {
+    pub fn open(path: &Path) -> Result<Self> {
+        let log = fs::read_to_string(path)
+            .with_context(|| format!("Could not read event log {}", path.display()))?;
+        let entries = log
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line)
+                    .with_context(|| format!("Line {} of {} is invalid", i + 1, path.display()))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            entries,
+            start: Instant::now(),
+        })
+    }
+
+    /// When the next event is due, if there is one left.
+    pub fn next_time(&self) -> Option<Instant> {
+        let entry = self.entries.front()?;
+        Some(self.start + Duration::from_secs_f64(entry.time))
+    }
+
+    /// Pops the next event if its time has come already.
+    pub fn next_due(&mut self) -> Option<RecordedEvent> {
+        if self.next_time()? > Instant::now() {
+            return None;
+        }
+        self.entries.pop_front().map(|entry| entry.event)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
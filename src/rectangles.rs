@@ -0,0 +1,119 @@
+//! A few rectangles at fixed pixel positions from the top left corner, which have to stay exactly
+//! where they are no matter how the window is resized.
+
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    vertex_attr_array, BlendState, Buffer, BufferAddress, BufferUsages, ColorTargetState,
+    ColorWrites, Device, FragmentState, MultisampleState, PipelineLayoutDescriptor, PrimitiveState,
+    RenderPass, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource,
+    TextureFormat, VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::globals::Globals;
+
+/// Left, top, width and height in physical pixels, and the color.
+const RECTANGLES: [([f32; 4], [f32; 4]); 3] = [
+    ([16.0, 16.0, 128.0, 64.0], [0.9, 0.2, 0.2, 1.0]),
+    ([96.0, 48.0, 64.0, 64.0], [0.2, 0.8, 0.3, 0.7]),
+    // a single pixel wide, so any blurring or off-by-one is obvious
+    ([16.0, 128.0, 1.0, 48.0], [1.0, 1.0, 1.0, 1.0]),
+];
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Vertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+pub struct Rectangles {
+    pipeline: RenderPipeline,
+    vertices: Buffer,
+    count: u32,
+}
+
+impl Rectangles {
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals: &Globals,
+    ) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("rectangles shader"),
+            source: ShaderSource::Wgsl(include_str!("rectangles.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("rectangles pipeline layout"),
+            bind_group_layouts: &[&globals.layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("rectangles pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[VertexBufferLayout {
+                    array_stride: mem::size_of::<Vertex>() as BufferAddress,
+                    step_mode: VertexStepMode::Vertex,
+                    attributes: &vertex_attr_array![0 => Float32x2, 1 => Float32x4],
+                }],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: sample_count,
+                ..MultisampleState::default()
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+
+        // they never move, the projection in the globals takes care of resizes
+        let vertices: Vec<_> = RECTANGLES
+            .iter()
+            .flat_map(|&([left, top, width, height], color)| {
+                let (right, bottom) = (left + width, top + height);
+                [
+                    [left, top],
+                    [left, bottom],
+                    [right, bottom],
+                    [left, top],
+                    [right, bottom],
+                    [right, top],
+                ]
+                .map(|position| Vertex { position, color })
+            })
+            .collect();
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("rectangle vertices"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: BufferUsages::VERTEX,
+        });
+
+        Self {
+            pipeline,
+            vertices: buffer,
+            count: vertices.len() as u32,
+        }
+    }
+
+    /// Expects the globals to be bound at group 0 already.
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertices.slice(..));
+        render_pass.draw(0..self.count, 0..1);
+    }
+}
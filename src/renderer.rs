@@ -0,0 +1,1139 @@
+//! Everything talking to the GPU for the windows, so `main.rs` only has to drive the event loop.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    error::Error,
+    fmt, iter,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use glam::Mat4;
+use pollster::FutureExt;
+use tracing::{error, info, warn};
+use wgpu::{
+    Adapter, Backends, Color, CommandEncoderDescriptor, CompositeAlphaMode, Device,
+    DeviceDescriptor, DownlevelFlags, ErrorFilter, Extent3d, Features, Instance,
+    InstanceDescriptor, Limits, PresentMode, Queue, RequestAdapterOptions, Surface,
+    SurfaceCapabilities, SurfaceConfiguration, SurfaceError, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureUsages, TextureView, TextureViewDescriptor,
+};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::TouchPhase,
+    window::Window,
+};
+
+use crate::{
+    canvas::Canvas,
+    diagnostics::Diagnostics,
+    globals::View,
+    gpu_timer::GpuTimer,
+    memory::{self, Allocation},
+    particles::Particles,
+    readback,
+    scene::{self, Scene},
+    stress::Stress,
+    tone_mapping::Operator,
+    Args,
+};
+
+pub struct Renderer {
+    /// Needed again for recreating the surfaces after a suspend.
+    instance: Instance,
+    adapter: Adapter,
+    device: Device,
+    queue: Queue,
+    /// One per window, in the same order as the windows were passed to [`Self::new`].
+    surfaces: Vec<WindowSurface>,
+
+    present_mode: PresentMode,
+    alpha_mode: CompositeAlphaMode,
+
+    gpu_timer: Option<GpuTimer>,
+    gpu_frame_time: Option<Duration>,
+    /// Set from wgpu's error handler, which might run on any thread.
+    device_lost: Arc<AtomicBool>,
+    startup: StartupTimes,
+}
+
+/// Why a frame couldn't be drawn, so the event loop can decide whether that's worth recovering
+/// from or not.
+#[derive(Debug)]
+pub enum RenderError {
+    /// The surface is gone or doesn't match the window anymore, configuring it again should fix
+    /// that.
+    SurfaceLost,
+    /// The next frame took too long to become available, the next try might work out.
+    Timeout,
+    /// There's no memory left for the next frame.
+    OutOfMemory,
+    /// Everything on the device is gone, the renderer needs to be recreated from scratch.
+    DeviceLost,
+    /// wgpu rejected something, like a surface configuration.
+    DeviceError(String),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SurfaceLost => write!(f, "surface was lost"),
+            Self::Timeout => write!(f, "timed out waiting for the next frame"),
+            Self::OutOfMemory => write!(f, "out of memory"),
+            Self::DeviceLost => write!(f, "device was lost"),
+            Self::DeviceError(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl Error for RenderError {}
+
+/// When the slow parts of [`Renderer::new`] were done.
+#[derive(Clone, Copy)]
+pub struct StartupTimes {
+    pub adapter: Instant,
+    pub device: Instant,
+}
+
+struct WindowSurface {
+    /// Gone while the app is suspended, as the OS might take the native window away.
+    surface: Option<Surface>,
+    capabilities: SurfaceCapabilities,
+    format: TextureFormat,
+    /// What the surface was last configured for, might be zero-sized while minimized.
+    size: PhysicalSize<u32>,
+    /// Counts the textures wgpu creates for the surface, `None` while there's no surface.
+    surface_memory: Option<Allocation>,
+    scene: Scene,
+    /// What the last frame was rendered at, so it can be rendered again the same way.
+    last_elapsed: Duration,
+}
+
+impl Renderer {
+    /// The adapter is picked to work with the first window, the others are assumed to work with
+    /// the same present and alpha mode, since they're all on the same adapter anyway.
+    ///
+    /// # Safety
+    ///
+    /// All `windows` have to outlive the returned renderer, as the surfaces refer to them.
+    pub unsafe fn new(args: &Args, windows: &[&Window]) -> Result<Self> {
+        let instance = create_instance(args);
+        let surfaces = windows
+            .iter()
+            // SAFETY: upheld by the caller
+            .map(|window| unsafe { instance.create_surface(*window) })
+            .collect::<Result<Vec<_>, _>>()?;
+        let first_surface = surfaces.first().context("Need at least one window")?;
+
+        if args.list_adapters {
+            list_adapters(&instance);
+        }
+
+        let adapter = request_adapter(&instance, args, Some(first_surface))?;
+        let adapter_ready = Instant::now();
+
+        // doesn't change as long as the adapter stays the same, no need to ask again later on
+        let capabilities = first_surface.get_capabilities(&adapter);
+        if args.print_caps {
+            print_surface_capabilities(&capabilities);
+        }
+
+        let format = scene::render_format(choose_surface_format(&capabilities)?, args);
+        let (device, queue) = request_device(&adapter, format, args)?;
+        let device_lost = watch_for_device_loss(&device);
+        let device_ready = Instant::now();
+
+        let present_mode =
+            choose_present_mode(&capabilities, args.present_mode, args.frames.is_some())?;
+
+        let alpha_mode = choose_alpha_mode(&capabilities, args.transparent)?;
+
+        let surfaces = surfaces
+            .into_iter()
+            .zip(windows)
+            .enumerate()
+            .map(|(index, (surface, window))| {
+                // the adapter was only picked for the first one
+                if !adapter.is_surface_supported(&surface) {
+                    bail!(
+                        "{} can't present to window {index}",
+                        adapter.get_info().name
+                    );
+                }
+                let capabilities = surface.get_capabilities(&adapter);
+                let format = choose_surface_format(&capabilities)?;
+                let size = configure_surface(
+                    &surface,
+                    &device,
+                    format,
+                    window.inner_size(),
+                    present_mode,
+                    alpha_mode,
+                )?;
+                let mut scene = Scene::new(&device, format, size, args)?;
+                if let Some(path) = &args.image {
+                    scene.image_display.load(&device, &queue, path)?;
+                }
+
+                Ok(WindowSurface {
+                    surface: Some(surface),
+                    capabilities,
+                    format,
+                    size,
+                    surface_memory: Some(memory::surface(format, size)),
+                    scene,
+                    last_elapsed: Duration::ZERO,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let gpu_timer = GpuTimer::new(&device, &queue);
+        info!("Textures take an estimated {}", memory::allocated());
+
+        Ok(Self {
+            instance,
+            adapter,
+            device,
+            queue,
+            surfaces,
+            present_mode,
+            alpha_mode,
+            gpu_timer,
+            gpu_frame_time: None,
+            device_lost,
+            startup: StartupTimes {
+                adapter: adapter_ready,
+                device: device_ready,
+            },
+        })
+    }
+
+    /// `window` is the index of the window in the order passed to [`Self::new`].
+    pub fn resize(&mut self, window: usize, size: PhysicalSize<u32>) -> Result<(), RenderError> {
+        let target = &mut self.surfaces[window];
+        // the surface is configured again on resume
+        let Some(surface) = target.surface.as_ref() else {
+            target.size = size;
+            return Ok(());
+        };
+        // happens while minimized, everything is recreated once the window is restored
+        let Some(size) = resize_scene(&mut target.scene, &self.device, &self.queue, size) else {
+            target.size = size;
+            return Ok(());
+        };
+
+        target.size = configure_surface(
+            surface,
+            &self.device,
+            target.format,
+            size,
+            self.present_mode,
+            self.alpha_mode,
+        )?;
+        target.surface_memory = Some(memory::surface(target.format, target.size));
+        info!(
+            "Resized window {window} to {}x{}, textures take an estimated {} now",
+            target.size.width,
+            target.size.height,
+            memory::allocated()
+        );
+        Ok(())
+    }
+
+    /// Renders and presents one frame into the given window. Returns whether a frame was
+    /// actually presented, if not, it's worth trying again next frame.
+    pub fn render(
+        &mut self,
+        window: usize,
+        clear_color: Color,
+        elapsed: Duration,
+    ) -> Result<bool, RenderError> {
+        if self.is_device_lost() {
+            return Err(RenderError::DeviceLost);
+        }
+
+        let target = &self.surfaces[window];
+        // minimized or suspended, there's nothing to render to
+        let Some(surface) = target
+            .surface
+            .as_ref()
+            .filter(|_| !is_zero_sized(target.size))
+        else {
+            return Ok(false);
+        };
+
+        let next_frame = surface.get_current_texture().map_err(|err| match err {
+            SurfaceError::Lost | SurfaceError::Outdated => RenderError::SurfaceLost,
+            SurfaceError::Timeout => RenderError::Timeout,
+            SurfaceError::OutOfMemory => RenderError::OutOfMemory,
+        })?;
+
+        let next_frame_view = next_frame.texture.create_view(&TextureViewDescriptor {
+            format: Some(target.format),
+            ..TextureViewDescriptor::default()
+        });
+
+        self.surfaces[window].last_elapsed = elapsed;
+        self.render_frame(window, &next_frame_view, clear_color, elapsed);
+        next_frame.present();
+
+        Ok(true)
+    }
+
+    /// Renders the frame [`Self::render`] last presented again, but into an offscreen texture of
+    /// the given size instead of the window. Returns it as tightly packed RGBA8 rows. Renders at
+    /// the same point in time, so nothing animated is moved along by it.
+    pub fn render_to_buffer(
+        &mut self,
+        window: usize,
+        width: u32,
+        height: u32,
+        clear_color: Color,
+    ) -> Result<Vec<u8>> {
+        let size = PhysicalSize::new(width, height);
+        if is_zero_sized(size) {
+            bail!("Cannot render into an empty texture");
+        }
+        let target = &mut self.surfaces[window];
+        let texture = memory::create_texture(
+            &self.device,
+            &TextureDescriptor {
+                label: Some("offscreen target"),
+                size: Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: target.format,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+                view_formats: &[],
+            },
+        );
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        // the MSAA target has to match the texture, afterwards it's back to matching the surface
+        let surface_size = target.size;
+        let elapsed = target.last_elapsed;
+        if size != surface_size {
+            target.scene.resize(&self.device, &self.queue, size);
+        }
+        self.render_frame(window, &view, clear_color, elapsed);
+        if size != surface_size {
+            let target = &mut self.surfaces[window];
+            target.scene.resize(&self.device, &self.queue, surface_size);
+        }
+
+        readback::read_texture_rgba(&self.device, &self.queue, &texture)
+    }
+
+    /// What [`Self::render`] and [`Self::render_to_buffer`] have in common, everything besides
+    /// where the frame ends up. `view` has to be of the window's format and of the size the scene
+    /// was last resized to.
+    fn render_frame(
+        &mut self,
+        window: usize,
+        view: &TextureView,
+        clear_color: Color,
+        elapsed: Duration,
+    ) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        if let Some(timer) = &self.gpu_timer {
+            timer.start(&mut encoder);
+        }
+        self.surfaces[window].scene.render(
+            &self.device,
+            &self.queue,
+            &mut encoder,
+            view,
+            clear_color,
+            elapsed,
+        );
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.end(&mut encoder);
+        }
+        self.queue.submit(iter::once(encoder.finish()));
+
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.submitted();
+            if let Some(gpu_frame_time) = timer.poll(&self.device) {
+                self.gpu_frame_time = Some(gpu_frame_time);
+            }
+        }
+    }
+
+    /// Renders the last frame again at the window's size and writes it to `path`.
+    pub fn screenshot(&mut self, window: usize, path: &str, clear_color: Color) -> Result<()> {
+        let size = self.surfaces[window].size;
+        if is_zero_sized(size) {
+            bail!("Window is minimized");
+        }
+        let pixels = self.render_to_buffer(window, size.width, size.height, clear_color)?;
+        readback::save_png(path, size.width, size.height, pixels)?;
+        info!("Saved screenshot to {path}");
+
+        Ok(())
+    }
+
+    /// Writes everything painted into the given window so far to `path`, without anything else
+    /// that's usually drawn.
+    pub fn save_canvas(&self, window: usize, path: &str) -> Result<()> {
+        let Some(canvas) = &self.surfaces[window].scene.canvas else {
+            bail!("Nothing was painted, that needs --paint");
+        };
+        let texture = canvas.texture();
+        let pixels = readback::read_texture_rgba(&self.device, &self.queue, texture)?;
+        readback::save_png(path, texture.width(), texture.height(), pixels)?;
+        info!("Saved canvas to {path}");
+
+        Ok(())
+    }
+
+    /// `window_size` is what the touch locations are relative to, which might be larger than the
+    /// surface if that had to be capped.
+    pub fn set_touches(
+        &mut self,
+        window: usize,
+        touches: &HashMap<u64, VecDeque<PhysicalPosition<f64>>>,
+        window_size: PhysicalSize<u32>,
+    ) {
+        self.surfaces[window].scene.touch_quads.update(
+            &self.device,
+            &self.queue,
+            touches,
+            window_size,
+        );
+    }
+
+    /// Drops all surfaces, as the windows they belong to might not be valid anymore until
+    /// [`Self::resume`] is called.
+    pub fn suspend(&mut self) {
+        for target in &mut self.surfaces {
+            target.surface = None;
+            target.surface_memory = None;
+        }
+    }
+
+    /// Creates and configures the surfaces dropped by [`Self::suspend`] again.
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`Self::new`], `windows` have to be the same as back then, in the same order.
+    pub unsafe fn resume(&mut self, windows: &[&Window]) -> Result<()> {
+        for (target, window) in self.surfaces.iter_mut().zip(windows) {
+            if target.surface.is_some() {
+                continue;
+            }
+
+            // SAFETY: upheld by the caller
+            let surface = unsafe { self.instance.create_surface(*window) }?;
+            let capabilities = surface.get_capabilities(&self.adapter);
+            // everything else was built for the old format already
+            if !capabilities.formats.contains(&target.format) {
+                bail!(
+                    "The new surface doesn't support {:?} anymore, only {:?}",
+                    target.format,
+                    capabilities.formats
+                );
+            }
+
+            target.size = configure_surface(
+                &surface,
+                &self.device,
+                target.format,
+                window.inner_size(),
+                self.present_mode,
+                self.alpha_mode,
+            )?;
+            target.surface_memory = Some(memory::surface(target.format, target.size));
+            target.scene.resize(&self.device, &self.queue, target.size);
+            target.capabilities = capabilities;
+            target.surface = Some(surface);
+        }
+        Ok(())
+    }
+
+    /// Applies to all windows, or none if `source` doesn't compile.
+    pub fn set_shader(&mut self, source: &str) -> Result<()> {
+        for target in &mut self.surfaces {
+            target.scene.set_shader(&self.device, source)?;
+        }
+        Ok(())
+    }
+
+    /// Shows the image at `path` in the given window instead of the triangle. If it can't be
+    /// loaded, whatever was shown before stays.
+    pub fn load_image(&mut self, window: usize, path: &Path) -> Result<()> {
+        self.surfaces[window]
+            .scene
+            .image_display
+            .load(&self.device, &self.queue, path)
+    }
+
+    /// Paints with the finger from the next frame on. Does nothing unless painting was asked for.
+    pub fn paint_touch(
+        &mut self,
+        window: usize,
+        id: u64,
+        phase: TouchPhase,
+        location: PhysicalPosition<f64>,
+    ) {
+        if let Some(canvas) = &mut self.surfaces[window].scene.canvas {
+            canvas.touch(id, phase, location);
+        }
+    }
+
+    /// Spawns a burst of particles at `location`. Does nothing unless particles were asked for and
+    /// are available. `elapsed` is the same as for [`Self::render`].
+    pub fn spawn_particles(
+        &mut self,
+        window: usize,
+        id: u64,
+        location: PhysicalPosition<f64>,
+        elapsed: Duration,
+    ) {
+        if let Some(particles) = &mut self.surfaces[window].scene.particles {
+            particles.spawn(&self.queue, id, location, elapsed);
+        }
+    }
+
+    /// How many quads the stress test in the given window draws, if there is one.
+    pub fn stress_count(&self, window: usize) -> Option<usize> {
+        self.surfaces[window]
+            .scene
+            .stress
+            .as_ref()
+            .map(Stress::count)
+    }
+
+    /// Whether the given window has particles that are still moving.
+    pub fn has_particles(&self, window: usize) -> bool {
+        self.surfaces[window]
+            .scene
+            .particles
+            .as_ref()
+            .is_some_and(Particles::is_alive)
+    }
+
+    /// Removes the last stroke painted in the given window. Returns `false` if there was none.
+    pub fn undo_stroke(&mut self, window: usize) -> bool {
+        self.surfaces[window]
+            .scene
+            .canvas
+            .as_mut()
+            .is_some_and(Canvas::undo)
+    }
+
+    /// Hides the HUD of the given window if `text` is `None`.
+    pub fn set_hud_text(&mut self, window: usize, text: Option<&str>) {
+        self.surfaces[window]
+            .scene
+            .hud
+            .set_text(&self.device, &self.queue, text);
+    }
+
+    pub fn set_view(&mut self, window: usize, view: View) {
+        self.surfaces[window].scene.globals.set_view(view);
+    }
+
+    pub fn set_view_projection(&mut self, window: usize, view_projection: Mat4) {
+        self.surfaces[window]
+            .scene
+            .globals
+            .set_view_projection(view_projection);
+    }
+
+    /// If so, nothing rendered with this renderer will show up anymore, it needs to be recreated
+    /// from scratch.
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
+
+    /// Short enough to fit into a window title, like `llvmpipe (Gl)`.
+    pub fn adapter_name(&self) -> String {
+        let info = self.adapter.get_info();
+        format!("{} ({:?})", info.name, info.backend)
+    }
+
+    /// Describes the first window's surface, the others are most likely the same anyway.
+    pub fn diagnostics(&self) -> Diagnostics {
+        Diagnostics::collect(
+            &self.adapter,
+            &self.device,
+            self.surfaces[0].format,
+            self.present_mode,
+        )
+    }
+
+    /// Shared by all windows.
+    pub fn present_mode(&self) -> PresentMode {
+        self.present_mode
+    }
+
+    pub fn surface_format(&self, window: usize) -> TextureFormat {
+        self.surfaces[window].format
+    }
+
+    /// What the surface of the window was last configured for, which lags behind the window
+    /// itself until pending resizes are applied.
+    pub fn surface_size(&self, window: usize) -> PhysicalSize<u32> {
+        self.surfaces[window].size
+    }
+
+    pub fn startup_times(&self) -> StartupTimes {
+        self.startup
+    }
+
+    /// How long the GPU took for the most recent frame that could be measured, if the adapter
+    /// supports measuring at all.
+    pub fn gpu_frame_time(&self) -> Option<Duration> {
+        self.gpu_frame_time
+    }
+
+    pub fn toggle_wireframe(&mut self) {
+        for target in &mut self.surfaces {
+            if !target.scene.toggle_wireframe() {
+                warn!("Cannot show wireframes, the device does not support POLYGON_MODE_LINE");
+                return;
+            }
+        }
+    }
+
+    /// What the device was created with, including everything asked for through --features that
+    /// the adapter supports.
+    pub fn features(&self) -> Features {
+        self.device.features()
+    }
+
+    /// Applies to all windows at once, 0 disables the blur.
+    pub fn set_blur_radius(&mut self, radius: u32) {
+        for target in &mut self.surfaces {
+            target
+                .scene
+                .blur
+                .set_radius(&self.device, &self.queue, radius);
+        }
+    }
+
+    /// Applies to all windows at once. Does nothing unless tone mapping was asked for.
+    pub fn set_tone_mapping(&mut self, operator: Operator) {
+        for target in &self.surfaces {
+            if let Some(tone_mapping) = &target.scene.tone_mapping {
+                tone_mapping.set_operator(&self.queue, operator);
+            }
+        }
+    }
+
+    /// Applies to all windows at once.
+    pub fn toggle_vsync(&mut self) -> Result<()> {
+        let supported = &self.surfaces[0].capabilities.present_modes;
+        let vsync_on = matches!(
+            self.present_mode,
+            PresentMode::Fifo | PresentMode::FifoRelaxed | PresentMode::AutoVsync
+        );
+
+        let target = if vsync_on {
+            [PresentMode::Mailbox, PresentMode::Immediate]
+                .into_iter()
+                .find(|mode| supported.contains(mode))
+        } else {
+            Some(PresentMode::Fifo)
+        };
+        let Some(target) = target else {
+            warn!("Cannot disable vsync, the surface only supports {supported:?}");
+            return Ok(());
+        };
+
+        self.set_present_mode(target)
+    }
+
+    /// Applies to all windows at once. Expects `present_mode` to be supported.
+    /// Only reconfigures the surfaces, nothing sized after them has to change. The old mode is
+    /// kept if any of them can't be reconfigured.
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) -> Result<()> {
+        info!("Switching present mode to {present_mode:?}");
+        if let Err(err) = self.configure_surfaces(present_mode) {
+            // the ones that did switch already shouldn't be left behind with a different mode
+            if let Err(err) = self.configure_surfaces(self.present_mode) {
+                error!("Could not switch back to {:?}: {err}", self.present_mode);
+            }
+            return Err(err.into());
+        }
+        self.present_mode = present_mode;
+        Ok(())
+    }
+
+    /// Configures every surface there is again at the size it has already, with `present_mode`.
+    fn configure_surfaces(&self, present_mode: PresentMode) -> Result<(), RenderError> {
+        for target in &self.surfaces {
+            // suspended or minimized, picks up the mode once it's configured again anyway
+            let Some(surface) = target
+                .surface
+                .as_ref()
+                .filter(|_| !is_zero_sized(target.size))
+            else {
+                continue;
+            };
+            configure_surface(
+                surface,
+                &self.device,
+                target.format,
+                target.size,
+                present_mode,
+                self.alpha_mode,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Limited to the backends in `args`, and with validation if asked for.
+pub fn create_instance(args: &Args) -> Instance {
+    if args.validation {
+        enable_validation();
+    }
+    Instance::new(InstanceDescriptor {
+        backends: args.backend.unwrap_or(Backends::all()),
+        ..InstanceDescriptor::default()
+    })
+}
+
+/// wgpu 0.16 has no instance flags yet, it only enables validation and debug labels on its own in
+/// debug builds. In release builds, the Vulkan loader can still be asked to load the validation
+/// layer, which then reports to stdout by itself.
+fn enable_validation() {
+    if cfg!(debug_assertions) {
+        info!("Validation is enabled");
+        return;
+    }
+
+    const LAYER: &str = "VK_LAYER_KHRONOS_validation";
+    let layers = env::var("VK_INSTANCE_LAYERS").unwrap_or_default();
+    if !layers.split(':').any(|layer| layer == LAYER) {
+        let layers = if layers.is_empty() {
+            LAYER.to_string()
+        } else {
+            format!("{layers}:{LAYER}")
+        };
+        // still before any other threads that might read the environment are started
+        env::set_var("VK_INSTANCE_LAYERS", layers);
+    }
+    warn!(
+        "Validation is enabled for Vulkan through {LAYER}, other backends only validate in debug \
+        builds"
+    );
+}
+
+/// Asks for an adapter matching `args`. Some setups refuse the first request even though there's a
+/// perfectly usable adapter, so this falls back to a fallback adapter and then to each backend on
+/// its own before giving up. If all of that fails, the error explains what was asked for and what
+/// would have been there.
+pub fn request_adapter(
+    instance: &Instance,
+    args: &Args,
+    compatible_surface: Option<&Surface>,
+) -> Result<Adapter> {
+    let options = RequestAdapterOptions {
+        power_preference: args.power.unwrap_or_default(),
+        force_fallback_adapter: args.force_fallback,
+        compatible_surface,
+    };
+    if let Some(adapter) = instance.request_adapter(&options).block_on() {
+        info!("Using adapter {}", describe_adapter(&adapter));
+        return Ok(adapter);
+    }
+
+    if !options.force_fallback_adapter {
+        let fallback_options = RequestAdapterOptions {
+            force_fallback_adapter: true,
+            ..options
+        };
+        if let Some(adapter) = instance.request_adapter(&fallback_options).block_on() {
+            warn!(
+                "Preferred adapter unavailable, using fallback adapter {}",
+                describe_adapter(&adapter)
+            );
+            return Ok(adapter);
+        }
+    }
+
+    let allowed = args.backend.unwrap_or(Backends::all());
+    for backend in [
+        Backends::VULKAN,
+        Backends::DX12,
+        Backends::METAL,
+        Backends::GL,
+    ] {
+        if !allowed.contains(backend) {
+            continue;
+        }
+        let adapter = instance.enumerate_adapters(backend).find(|adapter| {
+            compatible_surface.is_none_or(|surface| adapter.is_surface_supported(surface))
+        });
+        if let Some(adapter) = adapter {
+            warn!(
+                "Preferred adapter unavailable, trying {backend:?} on its own found {}",
+                describe_adapter(&adapter)
+            );
+            return Ok(adapter);
+        }
+    }
+
+    let backend = match args.backend {
+        Some(backend) => format!("{backend:?}"),
+        None => "any".to_string(),
+    };
+    let mut message = format!(
+        "Found no appropriate adapter (backend: {backend}, power preference: {:?}, \
+        fallback only: {}, compatible surface required: {})",
+        options.power_preference,
+        options.force_fallback_adapter,
+        compatible_surface.is_some(),
+    );
+    let available: Vec<_> = instance.enumerate_adapters(Backends::all()).collect();
+    if available.is_empty() {
+        message.push_str(", and there are no adapters at all for the enabled backends");
+    } else {
+        message.push_str(", available would have been:");
+        for adapter in &available {
+            message.push_str(&format!("\n- {}", describe_adapter(adapter)));
+        }
+    }
+    bail!(message)
+}
+
+/// Checks that the adapter can render `format` with the samples in `args` and requests a device
+/// with everything that needs, together with whichever of the features asked for the adapter
+/// supports.
+pub fn request_device(
+    adapter: &Adapter,
+    format: TextureFormat,
+    args: &Args,
+) -> Result<(Device, Queue)> {
+    let sample_count = args.samples;
+    let format_flags = adapter.get_texture_format_features(format).flags;
+    if !format_flags.sample_count_supported(sample_count) {
+        bail!("Adapter does not support {sample_count}x MSAA for {format:?}");
+    }
+
+    let mut features = Features::empty();
+    // WebGPU itself only guarantees 1 and 4 samples, anything else is adapter-specific
+    if !matches!(sample_count, 1 | 4) {
+        if !adapter
+            .features()
+            .contains(Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+        {
+            bail!(
+                "Adapter does not allow using {sample_count}x MSAA, only 1x and 4x are available"
+            );
+        }
+        features |= Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+    }
+    // optional, only used for measuring GPU frame times and the wireframe view respectively
+    features |= adapter.features() & (Features::TIMESTAMP_QUERY | Features::POLYGON_MODE_LINE);
+    if let Some(requested) = args.features {
+        let unsupported = requested - adapter.features();
+        if !unsupported.is_empty() {
+            warn!("Adapter does not support {unsupported:?}, leaving them out");
+        }
+        features |= requested & adapter.features();
+    }
+
+    let limits = match args.limits {
+        Some(preset) => preset_limits(adapter, preset)?,
+        None => limits(adapter),
+    };
+    info!("Requesting device with {features:?}");
+    adapter
+        .request_device(
+            &DeviceDescriptor {
+                label: None,
+                features,
+                limits,
+            },
+            None,
+        )
+        .block_on()
+        .with_context(|| {
+            format!(
+                "Found no appropriate device on {} with {features:?}",
+                describe_adapter(adapter)
+            )
+        })
+}
+
+/// One of the sets of limits wgpu has, to request the device with instead of the usual ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitsPreset {
+    Webgl2,
+    Downlevel,
+    Default,
+    /// Whatever the adapter can do.
+    Adapter,
+}
+
+/// Fails with everything the adapter falls short of if it can't meet `preset`.
+fn preset_limits(adapter: &Adapter, preset: LimitsPreset) -> Result<Limits> {
+    let supported = adapter.limits();
+    let limits = match preset {
+        LimitsPreset::Webgl2 => Limits::downlevel_webgl2_defaults(),
+        LimitsPreset::Downlevel => Limits::downlevel_defaults(),
+        LimitsPreset::Default => Limits::default(),
+        LimitsPreset::Adapter => return Ok(supported),
+    };
+
+    let mut exceeded = Vec::new();
+    limits.check_limits_with_fail_fn(&supported, false, |name, requested, allowed| {
+        exceeded.push(format!("{name} (needs {requested}, has {allowed})"));
+    });
+    if !exceeded.is_empty() {
+        bail!(
+            "{} does not support the {preset:?} limits: {}",
+            describe_adapter(adapter),
+            exceeded.join(", ")
+        );
+    }
+    Ok(limits)
+}
+
+/// The WebGL2 defaults, so everything runs wherever wgpu does. WebGL2 has no compute shaders at
+/// all though, so if the adapter can do them, the compute limits are taken from the next tier up.
+fn limits(adapter: &Adapter) -> Limits {
+    let webgl2 = Limits::downlevel_webgl2_defaults();
+    let compute = Limits::downlevel_defaults();
+    let with_compute = Limits {
+        max_storage_buffers_per_shader_stage: compute.max_storage_buffers_per_shader_stage,
+        max_storage_buffer_binding_size: compute.max_storage_buffer_binding_size,
+        max_compute_workgroup_storage_size: compute.max_compute_workgroup_storage_size,
+        max_compute_invocations_per_workgroup: compute.max_compute_invocations_per_workgroup,
+        max_compute_workgroup_size_x: compute.max_compute_workgroup_size_x,
+        max_compute_workgroup_size_y: compute.max_compute_workgroup_size_y,
+        max_compute_workgroup_size_z: compute.max_compute_workgroup_size_z,
+        max_compute_workgroups_per_dimension: compute.max_compute_workgroups_per_dimension,
+        ..webgl2
+    };
+
+    let has_compute = adapter
+        .get_downlevel_capabilities()
+        .flags
+        .contains(DownlevelFlags::COMPUTE_SHADERS);
+    if has_compute && with_compute.check_limits(&adapter.limits()) {
+        with_compute
+    } else {
+        info!("Adapter has no compute shaders, particles won't be available");
+        webgl2
+    }
+}
+
+/// wgpu 0.16 has no dedicated device-lost callback, a lost device only shows up as errors saying so.
+/// Everything else is only logged instead of panicking like wgpu would by default, a single
+/// validation error isn't worth taking the whole app down for.
+fn watch_for_device_loss(device: &Device) -> Arc<AtomicBool> {
+    let device_lost = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&device_lost);
+    device.on_uncaptured_error(Box::new(move |err| {
+        let mut source: Option<&dyn Error> = Some(&err);
+        let is_lost = iter::from_fn(|| {
+            let current = source?;
+            source = current.source();
+            Some(current)
+        })
+        // `DeviceError::Lost` in wgpu-core 0.16, which reads "Parent device is lost". Has to be
+        // checked again on every wgpu upgrade, otherwise lost devices go back to only being logged
+        .any(|err| err.to_string().contains("device is lost"));
+
+        if !is_lost {
+            error!("wgpu error: {err}");
+            return;
+        }
+        error!("{err}");
+        flag.store(true, Ordering::Relaxed);
+    }));
+    device_lost
+}
+
+/// Returns the size the surface actually ended up with, which is `size` capped to what the device
+/// can render. Fails if wgpu rejects the configuration, for example because of an unsupported
+/// format.
+fn configure_surface(
+    surface: &Surface,
+    device: &Device,
+    format: TextureFormat,
+    size: PhysicalSize<u32>,
+    present_mode: PresentMode,
+    alpha_mode: CompositeAlphaMode,
+) -> Result<PhysicalSize<u32>, RenderError> {
+    // zero-sized surfaces are invalid
+    let Some(size) = fit_target_size(device, size) else {
+        return Ok(size);
+    };
+
+    // wgpu would otherwise just panic on an invalid configuration
+    device.push_error_scope(ErrorFilter::Validation);
+    surface.configure(
+        device,
+        &SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: size.width,
+            height: size.height,
+            present_mode,
+            alpha_mode,
+            view_formats: Vec::new(),
+        },
+    );
+    if let Some(err) = device.pop_error_scope().block_on() {
+        return Err(RenderError::DeviceError(format!(
+            "Could not configure surface with {format:?} at {size:?}: {err}"
+        )));
+    }
+    Ok(size)
+}
+
+/// Resizes `scene` for a target of `size`, the same way for surfaces and headless targets. Returns
+/// what the target itself has to be created with, see [`fit_target_size`]. While that's `None`,
+/// the scene is left as it was, since there's nothing to render into anyway.
+pub fn resize_scene(
+    scene: &mut Scene,
+    device: &Device,
+    queue: &Queue,
+    size: PhysicalSize<u32>,
+) -> Option<PhysicalSize<u32>> {
+    let size = fit_target_size(device, size)?;
+    scene.resize(device, queue, size);
+    Some(size)
+}
+
+/// `size` capped to what the device can render. `None` if it's zero-sized, like while minimized,
+/// since no texture can be created with that.
+pub fn fit_target_size(device: &Device, size: PhysicalSize<u32>) -> Option<PhysicalSize<u32>> {
+    if is_zero_sized(size) {
+        return None;
+    }
+
+    let max = device.limits().max_texture_dimension_2d;
+    let clamped = PhysicalSize::new(size.width.min(max), size.height.min(max));
+    if clamped != size {
+        warn!(
+            "Target is {}x{}, but the device can only render up to {max}x{max}, \
+            capping it at {}x{}",
+            size.width, size.height, clamped.width, clamped.height,
+        );
+    }
+    Some(clamped)
+}
+
+pub fn is_zero_sized(size: PhysicalSize<u32>) -> bool {
+    size.width == 0 || size.height == 0
+}
+
+/// Prefers an sRGB format, so colors are blended and displayed as expected. Fails if there's no
+/// format at all, which happens if the adapter can't present to the surface.
+fn choose_surface_format(capabilities: &SurfaceCapabilities) -> Result<TextureFormat> {
+    let formats = &capabilities.formats;
+    let srgb = formats.iter().copied().find(TextureFormat::is_srgb);
+    srgb.or_else(|| formats.first().copied())
+        .context("Surface supports no formats on this adapter")
+}
+
+fn list_adapters(instance: &Instance) {
+    println!("Available adapters:");
+    for adapter in instance.enumerate_adapters(Backends::all()) {
+        println!("- {}", describe_adapter(&adapter));
+    }
+}
+
+fn describe_adapter(adapter: &Adapter) -> String {
+    let info = adapter.get_info();
+    format!(
+        "{} ({:?}, {:?}), driver: {} {}",
+        info.name, info.backend, info.device_type, info.driver, info.driver_info,
+    )
+}
+
+/// One entry per line, so the output can be diffed between machines.
+fn print_surface_capabilities(caps: &SurfaceCapabilities) {
+    println!("Surface capabilities:");
+    println!("  formats:");
+    for format in &caps.formats {
+        println!("  - {format:?}");
+    }
+    println!("  present modes:");
+    for mode in &caps.present_modes {
+        println!("  - {mode:?}");
+    }
+    println!("  alpha modes:");
+    for mode in &caps.alpha_modes {
+        println!("  - {mode:?}");
+    }
+    // wgpu doesn't report usages (yet), surface textures only ever support being rendered to
+    println!("  usages: {:?}", TextureUsages::RENDER_ATTACHMENT);
+}
+
+/// Tried in order if no present mode was requested explicitly. Fifo is always supported.
+const PRESENT_MODE_PREFERENCE: [PresentMode; 3] = [
+    PresentMode::Mailbox,
+    PresentMode::Immediate,
+    PresentMode::Fifo,
+];
+
+/// Without a `requested` one, `benchmark` prefers [`PresentMode::Immediate`], so nothing waits for
+/// the display.
+fn choose_present_mode(
+    capabilities: &SurfaceCapabilities,
+    requested: Option<PresentMode>,
+    benchmark: bool,
+) -> Result<PresentMode> {
+    let supported = &capabilities.present_modes;
+
+    if let Some(requested) = requested {
+        // the auto modes are resolved by wgpu itself and always work
+        let is_auto = matches!(requested, PresentMode::AutoVsync | PresentMode::AutoNoVsync);
+        if !is_auto && !supported.contains(&requested) {
+            bail!("Present mode {requested:?} is not supported, available are {supported:?}");
+        }
+        return Ok(requested);
+    }
+    if benchmark && supported.contains(&PresentMode::Immediate) {
+        return Ok(PresentMode::Immediate);
+    }
+
+    Ok(PRESENT_MODE_PREFERENCE
+        .into_iter()
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(PresentMode::Fifo))
+}
+
+/// Tried in order if the window should be transparent. Premultiplied is the only one where the
+/// compositor doesn't have to guess what the colors mean.
+const TRANSPARENT_ALPHA_MODE_PREFERENCE: [CompositeAlphaMode; 3] = [
+    CompositeAlphaMode::PreMultiplied,
+    CompositeAlphaMode::PostMultiplied,
+    CompositeAlphaMode::Inherit,
+];
+
+fn choose_alpha_mode(
+    capabilities: &SurfaceCapabilities,
+    transparent: bool,
+) -> Result<CompositeAlphaMode> {
+    if !transparent {
+        return Ok(CompositeAlphaMode::Auto);
+    }
+
+    let supported = &capabilities.alpha_modes;
+    TRANSPARENT_ALPHA_MODE_PREFERENCE
+        .into_iter()
+        .find(|mode| supported.contains(mode))
+        .with_context(|| {
+            format!("Surface cannot be transparent, it only supports the alpha modes {supported:?}")
+        })
+}
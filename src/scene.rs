@@ -0,0 +1,446 @@
+//! Everything that's drawn each frame, regardless of whether it ends up on a surface or in a
+//! plain texture.
+
+use std::{fs, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use pollster::FutureExt;
+use wgpu::{
+    Color, ColorTargetState, ColorWrites, CommandEncoder, Device, ErrorFilter, Extent3d, Features,
+    FragmentState, LoadOp, MultisampleState, Operations, PipelineLayoutDescriptor, PolygonMode,
+    PrimitiveState, Queue, RenderPass, RenderPassColorAttachment, RenderPassDescriptor,
+    RenderPipeline, RenderPipelineDescriptor, ShaderModule, ShaderModuleDescriptor, ShaderSource,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView,
+    TextureViewDescriptor, VertexState,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    blur::Blur,
+    canvas::Canvas,
+    depth::{self, DepthGeometry},
+    globals::Globals,
+    gradient::GradientBackground,
+    grid::Grid,
+    hud::Hud,
+    image_display::ImageDisplay,
+    memory::{self, TrackedTexture},
+    particles::Particles,
+    rectangles::Rectangles,
+    renderer::is_zero_sized,
+    stress::Stress,
+    tone_mapping::{ToneMapping, LINEAR_FORMAT},
+    touch::TouchQuads,
+    Args,
+};
+
+pub struct Scene {
+    pub globals: Globals,
+    /// Only there if a gradient was given.
+    gradient: Option<GradientBackground>,
+    /// Only there if a grid size was given.
+    grid: Option<Grid>,
+    pipeline: RenderPipeline,
+    /// Only there if the device supports [`Features::POLYGON_MODE_LINE`].
+    wireframe_pipeline: Option<RenderPipeline>,
+    wireframe: bool,
+    show_triangle: bool,
+    /// Drawn instead of the triangle, if asked for and the device can do it. Is the world if the
+    /// camera is used.
+    depth: Option<DepthGeometry>,
+    pub image_display: ImageDisplay,
+    /// Only there if asked for.
+    rectangles: Option<Rectangles>,
+    /// Only there if painting was asked for.
+    pub canvas: Option<Canvas>,
+    /// Only there if asked for and the device can do compute shaders.
+    pub particles: Option<Particles>,
+    /// Only there if asked for.
+    pub stress: Option<Stress>,
+    pub touch_quads: TouchQuads,
+    pub hud: Hud,
+    /// Does nothing while the radius is 0.
+    pub blur: Blur,
+    /// Only there if asked for, everything else renders in [`LINEAR_FORMAT`] then.
+    pub tone_mapping: Option<ToneMapping>,
+
+    format: TextureFormat,
+    /// Of the textures passed to [`Self::render`], as of the last resize.
+    size: PhysicalSize<u32>,
+    sample_count: u32,
+    // both are only present if sample_count > 1
+    msaa_texture: Option<TrackedTexture>,
+    msaa_view: Option<TextureView>,
+}
+
+impl Scene {
+    /// `format` and `size` are the ones of the textures later passed to [`Self::render`]. Anything
+    /// else about what to draw is taken from `args`, besides the image, which is loaded through
+    /// [`Self::image_display`] later on.
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        size: PhysicalSize<u32>,
+        args: &Args,
+    ) -> Result<Self> {
+        let sample_count = args.samples;
+        let tone_mapping = args
+            .tone_mapping
+            .map(|operator| ToneMapping::new(device, format, size, operator));
+        let target_format = format;
+        // from here on only the format everything besides tone mapping renders in matters
+        let format = render_format(format, args);
+        let globals = Globals::new(device, args.grid_size, args.gradient);
+        let gradient = args
+            .gradient
+            .map(|_| GradientBackground::new(device, format, sample_count, &globals));
+        let grid = args
+            .grid_size
+            .map(|_| Grid::new(device, format, sample_count, &globals));
+        let source = match &args.shader {
+            Some(path) => fs::read_to_string(path)
+                .with_context(|| format!("Could not read shader {}", path.display()))?,
+            None => include_str!("triangle.wgsl").to_string(),
+        };
+        let (pipeline, wireframe_pipeline) =
+            create_pipelines(device, format, sample_count, &globals, &source)?;
+        let image_display = ImageDisplay::new(device, format, sample_count, &globals);
+        let rectangles = args
+            .rectangles
+            .then(|| Rectangles::new(device, format, sample_count, &globals));
+        let canvas = args
+            .paint
+            .then(|| Canvas::new(device, format, sample_count, size, &globals));
+        let particles = args
+            .particles
+            .then(|| Particles::new(device, format, sample_count, &globals))
+            .flatten();
+        let stress = args
+            .stress
+            .map(|count| Stress::new(device, format, sample_count, &globals, count));
+        // drawn on top after all post-processing, straight into the target and without MSAA
+        let touch_quads = TouchQuads::new(device, target_format, 1);
+        let hud = Hud::new(device, target_format, 1, &globals);
+        let blur = Blur::new(device, format, size, args.blur);
+        let depth_shader = if args.camera {
+            Some(depth::Shader::World)
+        } else {
+            args.depth.then_some(depth::Shader::Overlap)
+        };
+        let depth = depth_shader.and_then(|shader| {
+            DepthGeometry::new(device, format, size, sample_count, &globals, shader)
+        });
+        let (msaa_texture, msaa_view) =
+            create_msaa_target(device, format, size, sample_count).unzip();
+
+        Ok(Self {
+            globals,
+            gradient,
+            grid,
+            pipeline,
+            wireframe_pipeline,
+            wireframe: false,
+            show_triangle: !args.no_triangle,
+            depth,
+            image_display,
+            rectangles,
+            canvas,
+            particles,
+            stress,
+            touch_quads,
+            hud,
+            blur,
+            tone_mapping,
+            format,
+            size,
+            sample_count,
+            msaa_texture,
+            msaa_view,
+        })
+    }
+
+    pub fn resize(&mut self, device: &Device, queue: &Queue, size: PhysicalSize<u32>) {
+        self.size = size;
+        (self.msaa_texture, self.msaa_view) =
+            create_msaa_target(device, self.format, size, self.sample_count).unzip();
+        if let Some(depth) = &mut self.depth {
+            depth.resize(device, size);
+        }
+        if let Some(canvas) = &mut self.canvas {
+            canvas.resize(device, queue, size);
+        }
+        self.blur.resize(device, queue, size);
+        if let Some(tone_mapping) = &mut self.tone_mapping {
+            tone_mapping.resize(device, size);
+        }
+    }
+
+    /// Replaces the shader of the triangle pipelines. If `source` doesn't compile, the old ones
+    /// are kept.
+    pub fn set_shader(&mut self, device: &Device, source: &str) -> Result<()> {
+        (self.pipeline, self.wireframe_pipeline) = create_pipelines(
+            device,
+            self.format,
+            self.sample_count,
+            &self.globals,
+            source,
+        )?;
+        Ok(())
+    }
+
+    /// Switches between drawing filled and outlined geometry. Returns `false` and does nothing if
+    /// the device can't draw outlines.
+    pub fn toggle_wireframe(&mut self) -> bool {
+        if self.wireframe_pipeline.is_none() {
+            return false;
+        }
+        self.wireframe = !self.wireframe;
+        true
+    }
+
+    /// Records a frame into `target`, with everything animated moved along to `elapsed`, the time
+    /// since the start. Whatever changed since the last frame is uploaded first. Every frame goes
+    /// through here, whether it ends up in a window, a screenshot or a headless render.
+    pub fn render(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        target: &TextureView,
+        clear_color: Color,
+        elapsed: Duration,
+    ) {
+        let size = self.size;
+        if let Some(canvas) = &mut self.canvas {
+            canvas.flush(device, queue);
+        }
+        if let Some(particles) = &mut self.particles {
+            particles.update(queue, elapsed);
+        }
+        if let Some(stress) = &mut self.stress {
+            stress.update(queue, elapsed, size);
+        }
+        self.globals.update(queue, elapsed, size);
+        self.record(encoder, target, clear_color);
+    }
+
+    /// Records the actual frame contents into `target`. With MSAA, everything is rendered into
+    /// the multisampled texture first and then resolved into `target`.
+    fn record(&self, encoder: &mut CommandEncoder, target: &TextureView, clear_color: Color) {
+        // each post-processing step renders into the target of the one before, the scene into the
+        // target of the first one
+        let output = target;
+        let linear = self.tone_mapping.as_ref().and_then(ToneMapping::input);
+        let blurred = linear.unwrap_or(output);
+        let target = self.blur.input().unwrap_or(blurred);
+        let (view, resolve_target) = match &self.msaa_view {
+            Some(msaa_view) => (msaa_view, Some(target)),
+            None => (target, None),
+        };
+        if let Some(particles) = &self.particles {
+            particles.simulate(encoder);
+        }
+
+        let mut render_pass =
+            self.begin_pass(encoder, view, resolve_target, LoadOp::Clear(clear_color));
+        if let Some(gradient) = &self.gradient {
+            gradient.draw(&mut render_pass);
+        }
+        if let Some(grid) = &self.grid {
+            grid.draw(&mut render_pass);
+        }
+        // the triangle would only be in the way of the image
+        if self.image_display.is_showing_image() {
+            self.image_display.draw(&mut render_pass);
+        } else if self.show_triangle && self.depth.is_none() {
+            let pipeline = match &self.wireframe_pipeline {
+                Some(wireframe_pipeline) if self.wireframe => wireframe_pipeline,
+                _ => &self.pipeline,
+            };
+            render_pass.set_pipeline(pipeline);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        // none of the other pipelines know about the depth attachment, so it needs a pass of its
+        // own and everything on top of it yet another one
+        let depth = self
+            .depth
+            .as_ref()
+            .filter(|_| !self.image_display.is_showing_image());
+        if let Some(depth) = depth {
+            drop(render_pass);
+            depth.draw(encoder, view, resolve_target, &self.globals.bind_group);
+            render_pass = self.begin_pass(encoder, view, resolve_target, LoadOp::Load);
+        }
+
+        if let Some(canvas) = &self.canvas {
+            canvas.draw(&mut render_pass);
+        }
+        if let Some(particles) = &self.particles {
+            particles.draw(&mut render_pass);
+        }
+        if let Some(rectangles) = &self.rectangles {
+            rectangles.draw(&mut render_pass);
+        }
+        if let Some(stress) = &self.stress {
+            stress.draw(&mut render_pass);
+        }
+
+        drop(render_pass);
+        self.blur.apply(encoder, blurred);
+        if let Some(tone_mapping) = &self.tone_mapping {
+            tone_mapping.apply(encoder, output);
+        }
+
+        // only the scene is post-processed, the overlays have to stay readable
+        let mut render_pass = self.begin_pass(encoder, output, None, LoadOp::Load);
+        self.touch_quads.draw(&mut render_pass);
+        self.hud.draw(&mut render_pass);
+    }
+
+    fn begin_pass<'a>(
+        &'a self,
+        encoder: &'a mut CommandEncoder,
+        view: &'a TextureView,
+        resolve_target: Option<&'a TextureView>,
+        load: LoadOp<Color>,
+    ) -> RenderPass<'a> {
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target,
+                ops: Operations { load, store: true },
+            })],
+            ..RenderPassDescriptor::default()
+        });
+        render_pass.set_bind_group(0, &self.globals.bind_group, &[]);
+        render_pass
+    }
+}
+
+/// What everything ends up rendered in before post-processing, when the final target has `format`.
+pub fn render_format(format: TextureFormat, args: &Args) -> TextureFormat {
+    if args.tone_mapping.is_some() {
+        LINEAR_FORMAT
+    } else {
+        format
+    }
+}
+
+/// The wireframe pipeline is only there if the device supports [`Features::POLYGON_MODE_LINE`].
+fn create_pipelines(
+    device: &Device,
+    format: TextureFormat,
+    sample_count: u32,
+    globals: &Globals,
+    source: &str,
+) -> Result<(RenderPipeline, Option<RenderPipeline>)> {
+    // wgpu would otherwise just panic on a shader that doesn't compile
+    device.push_error_scope(ErrorFilter::Validation);
+    let shader = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("triangle shader"),
+        source: ShaderSource::Wgsl(source.into()),
+    });
+    let pipeline = create_pipeline(
+        device,
+        format,
+        sample_count,
+        globals,
+        &shader,
+        PolygonMode::Fill,
+    );
+    let wireframe_pipeline = device
+        .features()
+        .contains(Features::POLYGON_MODE_LINE)
+        .then(|| {
+            create_pipeline(
+                device,
+                format,
+                sample_count,
+                globals,
+                &shader,
+                PolygonMode::Line,
+            )
+        });
+    if let Some(err) = device.pop_error_scope().block_on() {
+        bail!("Could not compile shader: {err}");
+    }
+
+    Ok((pipeline, wireframe_pipeline))
+}
+
+fn create_pipeline(
+    device: &Device,
+    format: TextureFormat,
+    sample_count: u32,
+    globals: &Globals,
+    shader: &ShaderModule,
+    polygon_mode: PolygonMode,
+) -> RenderPipeline {
+    let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("triangle pipeline layout"),
+        bind_group_layouts: &[&globals.layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("triangle pipeline"),
+        layout: Some(&layout),
+        vertex: VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        primitive: PrimitiveState {
+            polygon_mode,
+            ..PrimitiveState::default()
+        },
+        depth_stencil: None,
+        multisample: MultisampleState {
+            count: sample_count,
+            ..MultisampleState::default()
+        },
+        fragment: Some(FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(ColorTargetState {
+                format,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        multiview: None,
+    })
+}
+
+fn create_msaa_target(
+    device: &Device,
+    format: TextureFormat,
+    size: PhysicalSize<u32>,
+    sample_count: u32,
+) -> Option<(TrackedTexture, TextureView)> {
+    if sample_count == 1 || is_zero_sized(size) {
+        return None;
+    }
+
+    let texture = memory::create_texture(
+        device,
+        &TextureDescriptor {
+            label: Some("MSAA color target"),
+            size: Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+    );
+    let view = texture.create_view(&TextureViewDescriptor::default());
+
+    Some((texture, view))
+}
@@ -0,0 +1,42 @@
+//! Notices when the file given to `--shader` changes, so it can be reloaded without restarting.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tracing::error;
+use winit::event_loop::EventLoopProxy;
+
+use crate::UserEvent;
+
+/// Sends [`UserEvent::ShaderChanged`] whenever `path` is written to. Stops watching once the
+/// returned watcher is dropped.
+pub fn watch(path: &Path, proxy: EventLoopProxy<UserEvent>) -> Result<RecommendedWatcher> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("Could not find shader {}", path.display()))?;
+    // editors tend to replace the file instead of writing into it, which the watch on the file
+    // itself wouldn't survive
+    let directory = path
+        .parent()
+        .context("Shader has to be in a directory")?
+        .to_owned();
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let event = match event {
+            Ok(event) => event,
+            Err(err) => {
+                error!("Could not watch shader: {err}");
+                return;
+            }
+        };
+        let is_write = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_));
+        if is_write && event.paths.contains(&path) {
+            // the window might be closed already, nobody to tell then
+            let _ = proxy.send_event(UserEvent::ShaderChanged);
+        }
+    })?;
+    watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+
+    Ok(watcher)
+}
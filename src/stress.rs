@@ -0,0 +1,184 @@
+//! Lots of small quads bouncing around, all drawn in one instanced draw call. The positions are
+//! updated on the CPU and uploaded every frame, so this puts load on both the upload and the draw
+//! side.
+
+use std::{f32::consts::TAU, mem, time::Duration};
+
+use bytemuck::{Pod, Zeroable};
+use tracing::warn;
+use wgpu::{
+    vertex_attr_array, BlendState, Buffer, BufferAddress, BufferDescriptor, BufferUsages,
+    ColorTargetState, ColorWrites, Device, FragmentState, MultisampleState,
+    PipelineLayoutDescriptor, PrimitiveState, Queue, RenderPass, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, TextureFormat,
+    VertexBufferLayout, VertexState, VertexStepMode,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::globals::Globals;
+
+/// Fastest a quad crosses the window, in window sizes per second.
+const MAX_SPEED: f32 = 0.5;
+
+/// Laid out the same as in the shader.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct Instance {
+    /// Center in physical pixels from the top left corner.
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+/// Where a quad starts and where it's headed, relative to the window size so resizing doesn't
+/// leave any of them outside.
+struct Motion {
+    start: [f32; 2],
+    velocity: [f32; 2],
+}
+
+pub struct Stress {
+    pipeline: RenderPipeline,
+    buffer: Buffer,
+    motions: Vec<Motion>,
+    /// Reused every frame, only the positions change.
+    instances: Vec<Instance>,
+}
+
+impl Stress {
+    /// `count` is capped to what fits into a single buffer on the device.
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals: &Globals,
+        count: u32,
+    ) -> Self {
+        let max = device.limits().max_buffer_size / mem::size_of::<Instance>() as u64;
+        let count = if u64::from(count) > max {
+            warn!(
+                "Device can only fit {max} quads into one buffer, \
+                drawing that many instead of {count}"
+            );
+            max as usize
+        } else {
+            count as usize
+        };
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("stress shader"),
+            source: ShaderSource::Wgsl(include_str!("stress.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("stress pipeline layout"),
+            bind_group_layouts: &[&globals.layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("stress pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[VertexBufferLayout {
+                    array_stride: mem::size_of::<Instance>() as BufferAddress,
+                    step_mode: VertexStepMode::Instance,
+                    attributes: &vertex_attr_array![0 => Float32x2, 1 => Float32x4],
+                }],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: sample_count,
+                ..MultisampleState::default()
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("stress instances"),
+            size: (count * mem::size_of::<Instance>()) as BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // spread evenly instead of randomly, so every run looks the same. The start positions are
+        // the R2 sequence, which doesn't line up into visible patterns
+        let motions = (0..count)
+            .map(|i| {
+                let fraction = i as f32 / count as f32;
+                let angle = fraction * TAU * 7.0;
+                let speed = MAX_SPEED * (0.25 + 0.75 * fract(fraction * 13.0));
+                Motion {
+                    start: [fract(i as f32 * 0.754_877_7), fract(i as f32 * 0.569_840_3)],
+                    velocity: [angle.cos() * speed, angle.sin() * speed],
+                }
+            })
+            .collect();
+        let instances = (0..count)
+            .map(|i| Instance {
+                position: [0.0; 2],
+                color: hue_to_rgba(i as f32 / count as f32),
+            })
+            .collect();
+
+        Self {
+            pipeline,
+            buffer,
+            motions,
+            instances,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.instances.len()
+    }
+
+    /// Moves every quad to where it is `elapsed` after the start, bouncing off the edges of a
+    /// target of `size`.
+    pub fn update(&mut self, queue: &Queue, elapsed: Duration, size: PhysicalSize<u32>) {
+        let seconds = elapsed.as_secs_f32();
+        let size = [size.width as f32, size.height as f32];
+        for (instance, motion) in self.instances.iter_mut().zip(&self.motions) {
+            instance.position = [0, 1].map(|axis| {
+                bounce(motion.start[axis] + motion.velocity[axis] * seconds) * size[axis]
+            });
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&self.instances));
+    }
+
+    /// Expects the globals to be bound at group 0 already.
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        if self.instances.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.buffer.slice(..));
+        render_pass.draw(0..6, 0..self.instances.len() as u32);
+    }
+}
+
+fn fract(x: f32) -> f32 {
+    x - x.floor()
+}
+
+/// Folds `x` back into `0.0..=1.0` like something bouncing between 0 and 1 would.
+fn bounce(x: f32) -> f32 {
+    1.0 - (fract(x * 0.5) * 2.0 - 1.0).abs()
+}
+
+/// Fully saturated and bright, `hue` in `0.0..1.0`.
+fn hue_to_rgba(hue: f32) -> [f32; 4] {
+    let channel = |offset: f32| {
+        let x = fract(hue + offset) * 6.0;
+        ((x - 3.0).abs() - 1.0).clamp(0.0, 1.0)
+    };
+    [channel(0.0), channel(2.0 / 3.0), channel(1.0 / 3.0), 0.8]
+}
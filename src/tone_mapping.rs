@@ -0,0 +1,243 @@
+//! Renders everything into a linear floating-point target first, and only maps that into the
+//! range of the actual target at the very end with a selectable operator. Makes it obvious where
+//! colors are treated as linear or sRGB when they shouldn't be.
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType, BufferUsages,
+    Color, ColorTargetState, ColorWrites, CommandEncoder, Device, Extent3d, FragmentState, LoadOp,
+    MultisampleState, Operations, PipelineLayoutDescriptor, PrimitiveState, Queue,
+    RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureSampleType, TextureUsages, TextureView, TextureViewDescriptor,
+    TextureViewDimension, VertexState,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    memory::{self, TrackedTexture},
+    renderer::is_zero_sized,
+};
+
+/// What everything is rendered into before tone mapping. Linear and able to go above 1.
+pub const LINEAR_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// How colors above 1 are brought back into range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    /// Just clamped, like without tone mapping.
+    None,
+    Reinhard,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve.
+    Aces,
+}
+
+impl Operator {
+    /// The one after this, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            Self::None => Self::Reinhard,
+            Self::Reinhard => Self::Aces,
+            Self::Aces => Self::None,
+        }
+    }
+}
+
+/// Laid out the same as in the shader.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct Params {
+    /// The index of the [`Operator`].
+    operator: u32,
+    /// Whether the shader has to gamma encode by itself, since the target doesn't.
+    encode_srgb: u32,
+    _padding: [u32; 2],
+}
+
+pub struct ToneMapping {
+    pipeline: RenderPipeline,
+    layout: BindGroupLayout,
+    params: Buffer,
+    encode_srgb: bool,
+    /// Missing while the target is zero-sized. The texture is only kept to count it as allocated
+    /// for as long as the view is around.
+    input: Option<(TrackedTexture, TextureView, BindGroup)>,
+}
+
+impl ToneMapping {
+    /// `format` and `size` are the ones of the final target.
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        size: PhysicalSize<u32>,
+        operator: Operator,
+    ) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("tone mapping layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("tone mapping shader"),
+            source: ShaderSource::Wgsl(include_str!("tone_mapping.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("tone mapping pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("tone mapping pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            // anything multisampled is resolved before it ends up here
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+
+        let encode_srgb = !format.is_srgb();
+        let params = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("tone mapping params"),
+            contents: bytemuck::bytes_of(&Params::new(operator, encode_srgb)),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let mut tone_mapping = Self {
+            pipeline,
+            layout,
+            params,
+            encode_srgb,
+            input: None,
+        };
+        tone_mapping.resize(device, size);
+        tone_mapping
+    }
+
+    /// Recreates the linear target, it always has the size of the final one.
+    pub fn resize(&mut self, device: &Device, size: PhysicalSize<u32>) {
+        if is_zero_sized(size) {
+            self.input = None;
+            return;
+        }
+
+        let texture = memory::create_texture(
+            device,
+            &TextureDescriptor {
+                label: Some("linear target"),
+                size: Extent3d {
+                    width: size.width,
+                    height: size.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: LINEAR_FORMAT,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+        );
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("tone mapping bind group"),
+            layout: &self.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: self.params.as_entire_binding(),
+                },
+            ],
+        });
+        self.input = Some((texture, view, bind_group));
+    }
+
+    pub fn set_operator(&self, queue: &Queue, operator: Operator) {
+        queue.write_buffer(
+            &self.params,
+            0,
+            bytemuck::bytes_of(&Params::new(operator, self.encode_srgb)),
+        );
+    }
+
+    /// Where to render everything in [`LINEAR_FORMAT`], in place of the final target. `None`
+    /// while it's zero-sized.
+    pub fn input(&self) -> Option<&TextureView> {
+        self.input.as_ref().map(|(_, view, _)| view)
+    }
+
+    /// Maps whatever was rendered into [`Self::input`] into `target`.
+    pub fn apply(&self, encoder: &mut CommandEncoder, target: &TextureView) {
+        let Some((_, _, bind_group)) = &self.input else {
+            return;
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("tone mapping pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: Operations {
+                    // every pixel is overwritten anyway
+                    load: LoadOp::Clear(Color::TRANSPARENT),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+impl Params {
+    fn new(operator: Operator, encode_srgb: bool) -> Self {
+        Self {
+            operator: operator as u32,
+            encode_srgb: encode_srgb.into(),
+            _padding: [0; 2],
+        }
+    }
+}
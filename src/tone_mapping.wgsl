@@ -0,0 +1,50 @@
+struct Params {
+    // 0 is none, 1 Reinhard, 2 ACES
+    mapping: u32,
+    // whether the target doesn't gamma encode by itself
+    encode_srgb: u32,
+}
+
+@group(0) @binding(0)
+var linear: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> params: Params;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // a single triangle covering the whole target
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    return (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+}
+
+fn encode_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    // same size as the target, so no filtering needed
+    let color = textureLoad(linear, vec2<i32>(position.xy), 0);
+    var mapped = max(color.rgb, vec3<f32>(0.0));
+    switch params.mapping {
+        case 1u: {
+            mapped = mapped / (1.0 + mapped);
+        }
+        case 2u: {
+            mapped = aces(mapped);
+        }
+        default: {}
+    }
+    mapped = clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0));
+
+    if params.encode_srgb != 0u {
+        mapped = encode_srgb(mapped);
+    }
+    return vec4<f32>(mapped, color.a);
+}
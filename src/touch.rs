@@ -0,0 +1,167 @@
+//! Visualizes each finger currently on the screen as a colored square, following a trail of
+//! fading dots where it has been before.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    mem,
+};
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::{
+    vertex_attr_array, BlendState, Buffer, BufferAddress, BufferDescriptor, BufferUsages,
+    ColorTargetState, ColorWrites, Device, FragmentState, MultisampleState, PrimitiveState, Queue,
+    RenderPass, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource,
+    TextureFormat, VertexBufferLayout, VertexState, VertexStepMode,
+};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+/// Edge length of each square, in physical pixels.
+const QUAD_SIZE: f32 = 48.0;
+/// Same as [`QUAD_SIZE`], but for the dots of the trail.
+const TRAIL_DOT_SIZE: f32 = 8.0;
+
+const COLORS: [[f32; 4]; 6] = [
+    [0.9, 0.2, 0.2, 1.0],
+    [0.2, 0.8, 0.3, 1.0],
+    [0.2, 0.4, 0.9, 1.0],
+    [0.9, 0.8, 0.2, 1.0],
+    [0.8, 0.3, 0.9, 1.0],
+    [0.2, 0.8, 0.9, 1.0],
+];
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Instance {
+    center: [f32; 2],
+    half_extent: [f32; 2],
+    color: [f32; 4],
+}
+
+pub struct TouchQuads {
+    pipeline: RenderPipeline,
+    instances: Buffer,
+    capacity: usize,
+    count: u32,
+}
+
+impl TouchQuads {
+    pub fn new(device: &Device, format: TextureFormat, sample_count: u32) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("touch shader"),
+            source: ShaderSource::Wgsl(include_str!("touch.wgsl").into()),
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("touch pipeline"),
+            layout: None,
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[VertexBufferLayout {
+                    array_stride: mem::size_of::<Instance>() as BufferAddress,
+                    step_mode: VertexStepMode::Instance,
+                    attributes: &vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4],
+                }],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: sample_count,
+                ..MultisampleState::default()
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+
+        let capacity = 16;
+        Self {
+            pipeline,
+            instances: create_instance_buffer(device, capacity),
+            capacity,
+            count: 0,
+        }
+    }
+
+    /// Uploads where to draw the quads for this frame. Each trail ends with where the finger is
+    /// right now. `size` is the surface size the touch locations are relative to.
+    pub fn update(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        trails: &HashMap<u64, VecDeque<PhysicalPosition<f64>>>,
+        size: PhysicalSize<u32>,
+    ) {
+        let (width, height) = (size.width.max(1) as f32, size.height.max(1) as f32);
+        // y points down in window coordinates, but up in NDC
+        let to_ndc = |location: &PhysicalPosition<f64>| {
+            [
+                location.x as f32 / width * 2.0 - 1.0,
+                1.0 - location.y as f32 / height * 2.0,
+            ]
+        };
+
+        // all trails first, so no trail is drawn over another finger's square
+        let dots = trails.iter().flat_map(|(&id, trail)| {
+            let [r, g, b, _] = color(id);
+            let len = trail.len();
+            trail
+                .iter()
+                .take(len.saturating_sub(1))
+                .enumerate()
+                .map(move |(i, location)| Instance {
+                    center: to_ndc(location),
+                    half_extent: [TRAIL_DOT_SIZE / width, TRAIL_DOT_SIZE / height],
+                    // the older, the fainter
+                    color: [r, g, b, (i + 1) as f32 / len as f32],
+                })
+        });
+        let squares = trails.iter().filter_map(|(&id, trail)| {
+            Some(Instance {
+                center: to_ndc(trail.back()?),
+                half_extent: [QUAD_SIZE / width, QUAD_SIZE / height],
+                color: color(id),
+            })
+        });
+        let instances: Vec<_> = dots.chain(squares).collect();
+
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().next_power_of_two();
+            self.instances = create_instance_buffer(device, self.capacity);
+        }
+
+        queue.write_buffer(&self.instances, 0, bytemuck::cast_slice(&instances));
+        self.count = instances.len() as u32;
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        if self.count == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.instances.slice(..));
+        render_pass.draw(0..6, 0..self.count);
+    }
+}
+
+/// Cycles through [`COLORS`], so each finger keeps its color while it's down.
+pub fn color(id: u64) -> [f32; 4] {
+    COLORS[id as usize % COLORS.len()]
+}
+
+fn create_instance_buffer(device: &Device, capacity: usize) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("touch instances"),
+        size: (capacity * mem::size_of::<Instance>()) as BufferAddress,
+        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
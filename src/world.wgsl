@@ -0,0 +1,71 @@
+struct Globals {
+    time: f32,
+    grid_size: f32,
+    resolution: vec2<f32>,
+    gradient_start: vec4<f32>,
+    gradient_end: vec4<f32>,
+    gradient_horizontal: u32,
+    zoom: f32,
+    pan: vec2<f32>,
+    view_projection: mat4x4<f32>,
+    pixel_projection: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+}
+
+const TAU: f32 = 6.28318530718;
+// keep in sync with Shader::vertex_count in depth.rs
+const RING_TRIANGLES: u32 = 8u;
+const RING_RADIUS: f32 = 3.0;
+const FLOOR_SIZE: f32 = 6.0;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let triangle = index / 3u;
+    var world: vec3<f32>;
+
+    if triangle < RING_TRIANGLES {
+        // upright triangles all around the origin, facing it
+        let angle = f32(triangle) / f32(RING_TRIANGLES) * TAU;
+        let center = vec3<f32>(sin(angle), 0.0, -cos(angle)) * RING_RADIUS;
+        let tangent = vec3<f32>(cos(angle), 0.0, sin(angle));
+        var corners = array<vec3<f32>, 3>(
+            vec3<f32>(0.0, 0.5, 0.0),
+            vec3<f32>(-0.5, -0.5, 0.0),
+            vec3<f32>(0.5, -0.5, 0.0),
+        );
+        let corner = corners[index % 3u];
+        world = center + tangent * corner.x + vec3<f32>(0.0, corner.y, 0.0);
+        // a different hue for each, so it's easy to tell where one is looking
+        let hue = f32(triangle) / f32(RING_TRIANGLES);
+        out.color = clamp(abs(fract(hue + vec3<f32>(1.0, 2.0 / 3.0, 1.0 / 3.0)) * 6.0 - 3.0) - 1.0, vec3<f32>(0.0), vec3<f32>(1.0));
+    } else {
+        // two triangles making up the floor below everything
+        var corners = array<vec2<f32>, 6>(
+            vec2<f32>(-1.0, -1.0),
+            vec2<f32>(1.0, -1.0),
+            vec2<f32>(1.0, 1.0),
+            vec2<f32>(-1.0, -1.0),
+            vec2<f32>(1.0, 1.0),
+            vec2<f32>(-1.0, 1.0),
+        );
+        let corner = corners[index - RING_TRIANGLES * 3u] * FLOOR_SIZE;
+        world = vec3<f32>(corner.x, -0.5, corner.y);
+        out.color = vec3<f32>(0.35);
+    }
+
+    out.position = globals.view_projection * vec4<f32>(world, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
@@ -0,0 +1,229 @@
+//! Rendering without a window and checking the pixels that come out.
+
+use std::{env, iter, path::Path};
+
+use clap::Parser;
+use image::{Rgba, RgbaImage};
+use winit::dpi::PhysicalSize;
+use winit_testing_grounds::{headless, Args, DEFAULT_BACKGROUND_COLOR};
+
+/// How far off each channel may be, to allow for rounding in the sRGB conversion.
+const TOLERANCE: u8 = 1;
+
+/// How far a rendered frame may be from its golden image.
+#[derive(Clone, Copy)]
+struct GoldenTolerance {
+    /// Per channel, for a pixel to still count as the same.
+    per_channel: u8,
+    /// How many pixels may differ anyway, since GPUs don't all rasterize edges the same.
+    max_different_pixels: usize,
+}
+
+const GOLDEN_TOLERANCE: GoldenTolerance = GoldenTolerance {
+    per_channel: 2,
+    max_different_pixels: 16,
+};
+
+/// Size of all golden images, deliberately not square so aspect ratio bugs show up.
+const GOLDEN_SIZE: PhysicalSize<u32> = PhysicalSize::new(64, 48);
+
+/// The same conversion the GPU does when writing to an sRGB texture.
+fn linear_to_srgb(linear: f64) -> u8 {
+    let srgb = if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+    (srgb * 255.0).round() as u8
+}
+
+#[test]
+fn clears_to_the_default_background() {
+    let args = Args::parse_from(["winit-testing-grounds", "--no-triangle"]);
+    let pixels = headless::render(&args, PhysicalSize::new(4, 4)).unwrap();
+
+    let color = DEFAULT_BACKGROUND_COLOR;
+    let expected = [
+        linear_to_srgb(color.r),
+        linear_to_srgb(color.g),
+        linear_to_srgb(color.b),
+        (color.a * 255.0).round() as u8,
+    ];
+    assert_eq!(pixels.len(), 4 * 4 * 4);
+    for (i, pixel) in pixels.chunks_exact(4).enumerate() {
+        let matches = pixel
+            .iter()
+            .zip(expected)
+            .all(|(&actual, expected)| actual.abs_diff(expected) <= TOLERANCE);
+        assert!(matches, "pixel {i} is {pixel:?}, expected {expected:?}");
+    }
+}
+
+#[test]
+fn triangle_matches_golden() {
+    assert_matches_golden("triangle", &[], GOLDEN_TOLERANCE);
+}
+
+#[test]
+fn gradient_matches_golden() {
+    assert_matches_golden(
+        "gradient",
+        &[
+            "--no-triangle",
+            "--gradient",
+            "ff8000",
+            "0040ff",
+            "horizontal",
+        ],
+        GOLDEN_TOLERANCE,
+    );
+}
+
+#[test]
+fn grid_matches_golden() {
+    assert_matches_golden("grid", &["--grid-size", "8"], GOLDEN_TOLERANCE);
+}
+
+#[test]
+fn depth_matches_golden() {
+    assert_matches_golden("depth", &["--depth"], GOLDEN_TOLERANCE);
+}
+
+#[test]
+fn camera_matches_golden() {
+    assert_matches_golden("camera", &["--camera"], GOLDEN_TOLERANCE);
+}
+
+#[test]
+fn rectangles_match_golden() {
+    assert_matches_golden(
+        "rectangles",
+        &["--rectangles", "--no-triangle"],
+        GOLDEN_TOLERANCE,
+    );
+}
+
+#[test]
+fn blur_matches_golden() {
+    assert_matches_golden(
+        "blur",
+        &["--grid-size", "8", "--blur", "4"],
+        GOLDEN_TOLERANCE,
+    );
+}
+
+#[test]
+fn stress_matches_golden() {
+    assert_matches_golden(
+        "stress",
+        &["--stress", "64", "--no-triangle"],
+        GOLDEN_TOLERANCE,
+    );
+}
+
+#[test]
+fn tone_mapping_matches_golden() {
+    assert_matches_golden(
+        "tone_mapping",
+        &["--tone-mapping", "aces"],
+        GOLDEN_TOLERANCE,
+    );
+}
+
+/// The HUD is drawn after the blur, so it has to stay sharp while everything below is blurred.
+#[test]
+fn hud_over_blur_matches_golden() {
+    assert_matches_golden("hud_blur", &["--blur", "4", "--hud"], GOLDEN_TOLERANCE);
+}
+
+/// Minimizing resizes everything to 0x0, which can't be passed on to wgpu as is. Goes through the
+/// same scene resizing and size fitting a window does, and everything that's sized after the target
+/// is enabled, so none of it gets to skip the resize. Configuring the surface itself can't be
+/// covered without a window.
+#[test]
+fn survives_resizing_to_zero() {
+    let args = Args::parse_from([
+        "winit-testing-grounds",
+        "--samples",
+        "4",
+        "--depth",
+        "--paint",
+        "--blur",
+        "2",
+        "--tone-mapping",
+        "none",
+    ]);
+    let sizes = [GOLDEN_SIZE, PhysicalSize::new(0, 0), GOLDEN_SIZE];
+    let resized = headless::render_resized(&args, &sizes).unwrap();
+
+    let direct = headless::render(&args, GOLDEN_SIZE).unwrap();
+    assert!(resized == direct, "frame after the resizes differs");
+}
+
+/// Renders a frame with `flags` and compares it against `tests/golden/{name}.png`. With
+/// `UPDATE_GOLDEN=1`, the golden image is overwritten with the frame instead.
+///
+/// On failure, the frame and an image with all differing pixels in red end up next to each other
+/// in cargo's temporary directory for integration tests.
+fn assert_matches_golden(name: &str, flags: &[&str], tolerance: GoldenTolerance) {
+    let args = Args::parse_from(iter::once("winit-testing-grounds").chain(flags.iter().copied()));
+    let size = GOLDEN_SIZE;
+    let pixels = headless::render(&args, size).unwrap();
+    let actual = RgbaImage::from_raw(size.width, size.height, pixels).unwrap();
+
+    let golden_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.png"));
+    if env::var_os("UPDATE_GOLDEN").is_some_and(|update| update == "1") {
+        actual.save(&golden_path).unwrap();
+        return;
+    }
+
+    let golden = image::open(&golden_path)
+        .unwrap_or_else(|err| {
+            panic!(
+                "Could not open {}, run with UPDATE_GOLDEN=1 to create it: {err}",
+                golden_path.display()
+            )
+        })
+        .to_rgba8();
+    assert_eq!(
+        golden.dimensions(),
+        actual.dimensions(),
+        "{name} changed size"
+    );
+
+    let mut diff = RgbaImage::new(size.width, size.height);
+    let mut different = 0;
+    for ((golden, actual), diff) in golden.pixels().zip(actual.pixels()).zip(diff.pixels_mut()) {
+        let is_different = golden
+            .0
+            .iter()
+            .zip(actual.0)
+            .any(|(&golden, actual)| golden.abs_diff(actual) > tolerance.per_channel);
+        *diff = if is_different {
+            different += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            // dimmed, so the red stands out
+            let [r, g, b, _] = actual.0;
+            Rgba([r / 4, g / 4, b / 4, 255])
+        };
+    }
+
+    if different > tolerance.max_different_pixels {
+        let out = Path::new(env!("CARGO_TARGET_TMPDIR"));
+        let actual_path = out.join(format!("{name}-actual.png"));
+        let diff_path = out.join(format!("{name}-diff.png"));
+        actual.save(&actual_path).unwrap();
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "{different} pixels differ from {}, more than the allowed {}. The frame is at {}, \
+            the differences at {}",
+            golden_path.display(),
+            tolerance.max_different_pixels,
+            actual_path.display(),
+            diff_path.display(),
+        );
+    }
+}